@@ -6,15 +6,19 @@ use bevy::{
     math::Vec2,
     render::{
         color::Color,
+        texture::ImageLoaderSettings,
         view::{Msaa, NoFrustumCulling},
     },
     sprite::{Sprite, SpriteBundle},
-    transform::components::Transform,
+    transform::{components::Transform, TransformBundle},
     window::{PresentMode, Window, WindowPlugin, WindowResolution},
     DefaultPlugins,
 };
 use bevy_incandescent::{
-    ecs::{PointLight2d, PointLight2dBundle, ShadowCaster2dBundle, SpotLight2d, SpotLight2dBundle},
+    ecs::{
+        NormalMap2d, PointLight2d, PointLight2dBundle, ShadowCaster2dBundle, ShadowCasterShape2d,
+        SpecularMap2d, SpotLight2d, SpotLight2dBundle,
+    },
     math::CircularSector,
     IncandescentPlugin,
 };
@@ -93,12 +97,45 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         ShadowCaster2dBundle::default(),
     ));
 
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(150.)),
+                ..Default::default()
+            },
+            texture: asset_server.load("pbr/suzzane-color.png"),
+            transform: Transform::from_xyz(150., 100., 0.),
+            ..Default::default()
+        },
+        // Normal and specular maps hold data rather than colors.
+        NormalMap2d(asset_server.load_with_settings(
+            "pbr/suzzane-normal.png",
+            |settings: &mut ImageLoaderSettings| settings.is_srgb = false,
+        )),
+        // Raised parts are shinier.
+        SpecularMap2d(asset_server.load_with_settings(
+            "pbr/suzzane-height.png",
+            |settings: &mut ImageLoaderSettings| settings.is_srgb = false,
+        )),
+    ));
+
+    // An invisible wall.
+    commands.spawn((
+        ShadowCasterShape2d::Capsule {
+            half_length: 40.,
+            radius: 10.,
+        },
+        TransformBundle::from_transform(Transform::from_xyz(-150., 50., 0.)),
+    ));
+
     commands.spawn(PointLight2dBundle {
         point_light: PointLight2d {
             color: Color::rgb(rd.gen(), rd.gen(), rd.gen()),
             intensity: 1.,
             range: 200.,
             radius: 50.,
+            height: 50.,
+            ..Default::default()
         },
         transform: Transform::from_xyz(50., 25., 0.),
         ..Default::default()
@@ -114,6 +151,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 start: std::f32::consts::PI + std::f32::consts::FRAC_PI_4,
                 end: std::f32::consts::TAU - std::f32::consts::FRAC_PI_6,
             },
+            ..Default::default()
         },
         transform: Transform::from_xyz(-50., -25., 0.),
         ..Default::default()
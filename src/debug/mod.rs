@@ -6,12 +6,12 @@ use bevy::{
         system::Query,
     },
     gizmos::gizmos::Gizmos,
-    math::Vec3Swizzles,
+    math::{EulerRot, Vec3, Vec3Swizzles},
     render::view::VisibleEntities,
     transform::components::GlobalTransform,
 };
 
-use crate::ecs::{PointLight2d, SpotLight2d};
+use crate::ecs::{LineLight2d, PointLight2d, SpotLight2d};
 
 pub struct IncandescentDebugPlugin;
 
@@ -31,13 +31,39 @@ fn draw_light_range(
     mut gizmos: Gizmos,
     point_lights_query: Query<(&GlobalTransform, &PointLight2d)>,
     spot_lights_query: Query<(&GlobalTransform, &SpotLight2d)>,
+    line_lights_query: Query<(&GlobalTransform, &LineLight2d)>,
 ) {
     for (transform, light) in point_lights_query.iter() {
-        gizmos.circle_2d(transform.translation().xy(), light.range, light.color);
+        match light.elliptical_range {
+            Some(elliptical_range) => {
+                let rotation = transform
+                    .to_scale_rotation_translation()
+                    .1
+                    .to_euler(EulerRot::ZYX)
+                    .0;
+                gizmos.ellipse_2d(
+                    transform.translation().xy(),
+                    rotation + elliptical_range.rotation,
+                    elliptical_range.range,
+                    light.color,
+                );
+            }
+            None => {
+                gizmos.circle_2d(transform.translation().xy(), light.range, light.color);
+            }
+        }
     }
     for (transform, light) in spot_lights_query.iter() {
         gizmos.circle_2d(transform.translation().xy(), light.range, light.color);
     }
+    for (transform, light) in line_lights_query.iter() {
+        let half_segment = Vec3::new(light.length / 2., 0., 0.);
+        gizmos.line_2d(
+            transform.transform_point(-half_segment).xy(),
+            transform.transform_point(half_segment).xy(),
+            light.color,
+        );
+    }
 }
 
 fn print_light_visible_entities(
@@ -13,11 +13,20 @@ pub struct MainShadowCameraDriver;
 pub struct ShadowMap2dConfig {
     pub near: f32,
     pub far: f32,
+    /// Rounded up to the next power of 2.
     pub size: u32,
     pub offset: Vec2,
     pub bias: f32,
     pub alpha_threshold: f32,
     pub pcf: PcfConfig,
+    /// Contact hardening soft shadows, `None` to use a fixed PCF radius.
+    pub pcss: Option<PcssConfig>,
+    /// How the shadow map is filtered. `pcf` and `pcss` only take effect
+    /// when using [`ShadowFilter2d::Pcf`].
+    pub filter: ShadowFilter2d,
+    /// How many times the alpha map is sampled when casting shadows
+    /// for directional lights.
+    pub directional_shadow_steps: u32,
 }
 
 impl Default for ShadowMap2dConfig {
@@ -30,6 +39,9 @@ impl Default for ShadowMap2dConfig {
             bias: 0.005,
             alpha_threshold: 0.9,
             pcf: Default::default(),
+            pcss: None,
+            filter: ShadowFilter2d::Pcf,
+            directional_shadow_steps: 64,
         }
     }
 }
@@ -40,6 +52,36 @@ impl ShadowMap2dConfig {
     }
 }
 
+/// Per light overrides of [`ShadowMap2dConfig`]. Fields left as `None` fall back
+/// to the global config.
+#[derive(Component, Default, Clone, Copy, Reflect)]
+pub struct LightShadowSettings2d {
+    pub size: Option<u32>,
+    pub offset: Option<Vec2>,
+    pub bias: Option<f32>,
+    pub alpha_threshold: Option<f32>,
+    /// The poisson disk is shared between all lights, so `seed` is ignored here.
+    pub pcf: Option<PcfConfig>,
+    /// `Some(None)` disables PCSS for this light even if the global config enables it.
+    pub pcss: Option<Option<PcssConfig>>,
+    pub filter: Option<ShadowFilter2d>,
+}
+
+impl LightShadowSettings2d {
+    pub fn apply(&self, config: &ShadowMap2dConfig) -> ShadowMap2dConfig {
+        ShadowMap2dConfig {
+            size: self.size.unwrap_or(config.size),
+            offset: self.offset.unwrap_or(config.offset),
+            bias: self.bias.unwrap_or(config.bias),
+            alpha_threshold: self.alpha_threshold.unwrap_or(config.alpha_threshold),
+            pcf: self.pcf.unwrap_or(config.pcf),
+            pcss: self.pcss.unwrap_or(config.pcss),
+            filter: self.filter.unwrap_or(config.filter),
+            ..*config
+        }
+    }
+}
+
 #[derive(Clone, Copy, Reflect)]
 pub struct PcfConfig {
     pub seed: u32,
@@ -56,3 +98,55 @@ impl Default for PcfConfig {
         }
     }
 }
+
+/// Percentage-closer soft shadows. The penumbra grows with the distance between
+/// the caster and the receiver and with the radius of the light.
+///
+/// `PcfConfig::radius` is used as the minimum filter radius.
+#[derive(Clone, Copy, Reflect)]
+pub struct PcssConfig {
+    /// Samples taken to estimate the average distance of the blockers.
+    pub blocker_samples: u32,
+    /// Upper bound of the filter radius in pixels.
+    pub max_radius: f32,
+}
+
+impl Default for PcssConfig {
+    fn default() -> Self {
+        Self {
+            blocker_samples: 16,
+            max_radius: 32.,
+        }
+    }
+}
+
+/// The moment based filters store moments of the caster distance in the shadow map,
+/// and blur them along the rays of the light instead of taking poisson samples.
+///
+/// `blur_radius` is in texels of the shadow map.
+#[derive(Default, Clone, Copy, Reflect)]
+pub enum ShadowFilter2d {
+    /// Percentage-closer filtering.
+    #[default]
+    Pcf,
+    /// Variance shadow maps.
+    Variance {
+        blur_radius: u32,
+        /// Avoids artifacts on flat surfaces, usually a tiny value like `0.00002`.
+        min_variance: f32,
+        /// Cuts off the tail of the Chebyshev bound to reduce light bleeding, in `[0, 1)`.
+        light_bleeding_reduction: f32,
+    },
+    /// Exponential shadow maps.
+    Exponential {
+        blur_radius: u32,
+        /// Higher values give sharper shadows. Keep it below 80 to avoid overflowing.
+        exponent: f32,
+    },
+    /// Hamburger 4-moment shadow maps.
+    Moment {
+        blur_radius: u32,
+        /// Pulls the moments towards a constant to keep them stable, usually around `0.00003`.
+        moment_bias: f32,
+    },
+}
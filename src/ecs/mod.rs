@@ -1,21 +1,25 @@
 use bevy::{
     app::{App, Plugin},
+    asset::Handle,
     ecs::{bundle::Bundle, component::Component, reflect::ReflectResource, system::Resource},
+    math::Vec2,
     reflect::Reflect,
     render::{
         color::Color,
         extract_resource::ExtractResource,
         primitives::Frustum,
-        texture::ColorAttachment,
+        texture::{ColorAttachment, Image},
         view::{InheritedVisibility, ViewVisibility, Visibility, VisibleEntities},
     },
     transform::components::{GlobalTransform, Transform},
 };
 
-use crate::math::CircularSector;
+use crate::math::{triangulate_convex, triangulate_polygon, CircularSector};
 
 #[cfg(feature = "catalinzz")]
 pub mod catalinzz;
+#[cfg(feature = "radiance_cascades")]
+pub mod radiance_cascades;
 #[cfg(feature = "ray_marching")]
 pub mod ray_marching;
 
@@ -24,8 +28,21 @@ pub struct IncandescentEcsPlugin;
 impl Plugin for IncandescentEcsPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<PointLight2d>()
+            .register_type::<EllipticalRange2d>()
             .register_type::<SpotLight2d>()
-            .register_type::<AmbientLight2d>();
+            .register_type::<LightFalloff2d>()
+            .register_type::<DirectionalLight2d>()
+            .register_type::<LineLight2d>()
+            .register_type::<NormalMap2d>()
+            .register_type::<SpecularMap2d>()
+            .register_type::<Emissive2d>()
+            .register_type::<Unlit2d>()
+            .register_type::<LightReceiver2d>()
+            .register_type::<ShadowCasterShape2d>()
+            .register_type::<OccluderTexture2d>()
+            .register_type::<TranslucentShadowCaster2d>()
+            .register_type::<AmbientLight2d>()
+            .register_type::<Lighting2dApproach>();
     }
 }
 
@@ -34,26 +51,349 @@ pub struct ShadowView2d {
     pub attachment: ColorAttachment,
 }
 
-#[derive(Component, Default, Clone, Copy, Reflect)]
+/// Chooses how the lighting and shadows of a camera are rendered, among the approaches
+/// enabled by cargo features. Can be changed at any time.
+///
+/// Cameras without it use the first enabled approach, in the order below.
+/// So do cameras asking for an approach whose feature is disabled, with a warning.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum Lighting2dApproach {
+    /// Requires the `catalinzz` feature.
+    Catalinzz,
+    /// Requires the `ray_marching` feature.
+    RayMarching,
+    /// Requires the `geometry` feature.
+    ///
+    /// Sprites cast the shadow of their whole rectangle, use [`ShadowCasterShape2d`]
+    /// for other silhouettes. [`LineLight2d`]s and [`TranslucentShadowCaster2d`]s
+    /// are ignored.
+    Geometry,
+}
+
+impl Lighting2dApproach {
+    /// All approaches enabled by cargo features.
+    pub const ENABLED: &'static [Self] = &[
+        #[cfg(feature = "catalinzz")]
+        Self::Catalinzz,
+        #[cfg(feature = "ray_marching")]
+        Self::RayMarching,
+        #[cfg(feature = "geometry")]
+        Self::Geometry,
+    ];
+
+    /// Whether the feature of this approach is enabled.
+    #[inline]
+    pub fn is_enabled(self) -> bool {
+        Self::ENABLED.contains(&self)
+    }
+
+    /// Whether [`LineLight2d`]s are rendered by this approach.
+    #[inline]
+    pub fn renders_line_lights(self) -> bool {
+        self == Self::RayMarching
+    }
+}
+
+impl Default for Lighting2dApproach {
+    fn default() -> Self {
+        Self::ENABLED[0]
+    }
+}
+
+#[derive(Component, Default, Clone, Reflect)]
 pub struct PointLight2d {
     pub color: Color,
+    /// Values above 1 are kept by hdr cameras, through tonemapping and bloom.
     pub intensity: f32,
     pub range: f32,
     pub radius: f32,
+    /// Virtual height above the sprites in world space, lighting [`NormalMap2d`]s.
+    pub height: f32,
+    pub falloff: LightFalloff2d,
+    /// Texture projected through the light, covering its whole range
+    /// and rotating with the light.
+    pub cookie: Option<Handle<Image>>,
+    /// Stretches the light into an ellipse, overriding `range`.
+    pub elliptical_range: Option<EllipticalRange2d>,
 }
 
-#[derive(Component, Default, Clone, Copy, Reflect)]
+impl PointLight2d {
+    /// The distance from the light where it completely fades out in every direction.
+    pub fn max_range(&self) -> f32 {
+        match self.elliptical_range {
+            Some(elliptical_range) => elliptical_range.range.max_element(),
+            None => self.range,
+        }
+    }
+}
+
+/// Elliptical range of a [`PointLight2d`], like the oval light pools of car headlights
+/// or lamps squashed by an isometric perspective.
+///
+/// `radius`, the falloff and the cookie are stretched along with the range,
+/// while shadows are still casted from the center of the light.
+///
+/// With `catalinzz`, the shadow map still covers the circle of [`PointLight2d::max_range`],
+/// so the shadows of very flat ellipses are rendered at a lower resolution.
+#[derive(Clone, Copy, Reflect)]
+pub struct EllipticalRange2d {
+    /// The range along the x and y axes of the ellipse.
+    pub range: Vec2,
+    /// Counter clockwise rotation of the ellipse in radians,
+    /// relative to the rotation of the entity.
+    pub rotation: f32,
+}
+
+impl Default for EllipticalRange2d {
+    fn default() -> Self {
+        Self::new(Vec2::splat(100.))
+    }
+}
+
+impl EllipticalRange2d {
+    pub fn new(range: Vec2) -> Self {
+        Self {
+            range,
+            rotation: 0.,
+        }
+    }
+}
+
+#[derive(Component, Default, Clone, Reflect)]
 pub struct SpotLight2d {
     pub color: Color,
     pub intensity: f32,
     pub range: f32,
     pub radius: f32,
+    /// Virtual height above the sprites in world space, lighting [`NormalMap2d`]s.
+    pub height: f32,
+    /// Relative to the rotation of the entity unless `absolute_sector` is set.
     pub sector: CircularSector,
+    /// Interpret `sector` in world space, ignoring the rotation of the entity.
+    pub absolute_sector: bool,
+    /// Half angle of the inner cone where the light is at full intensity,
+    /// fading out smoothly towards the edge of `sector`. `None` gives a hard edge.
+    pub inner_extent: Option<f32>,
+    pub falloff: LightFalloff2d,
+    /// Texture projected through the light, covering its whole range
+    /// and rotating with the light.
+    pub cookie: Option<Handle<Image>>,
+}
+
+/// How the light fades out between its `radius` and `range`.
+#[derive(Default, Clone, Reflect)]
+pub enum LightFalloff2d {
+    #[default]
+    Linear,
+    /// Eases in and out, following a smoothstep curve.
+    Smooth,
+    /// Inverse square falloff starting from `radius`, windowed to reach zero at `range`
+    /// like lights in `bevy_pbr`. Use a non-zero `radius` to avoid a tiny hot spot.
+    InverseSquare,
+    /// Intensities sampled evenly from `radius` to `range`, linearly interpolated in between.
+    Custom(Vec<f32>),
+}
+
+/// A light emitted from a segment, like neon tubes.
+///
+/// The segment lies on the local x axis and is centered at the entity.
+///
+/// Only rendered when using `ray_marching`, other approaches ignore it
+/// and warn once, see [`Lighting2dApproach::renders_line_lights`].
+#[derive(Component, Default, Clone, Copy, Reflect)]
+pub struct LineLight2d {
+    pub color: Color,
+    pub intensity: f32,
+    pub length: f32,
+    /// Points closer than this to the segment are fully lit.
+    pub thickness: f32,
+    /// The distance from the segment where the light completely fades out.
+    pub range: f32,
+}
+
+/// A light that lits the whole view from a single direction, like the sun or the moon.
+#[derive(Component, Clone, Copy, Reflect)]
+pub struct DirectionalLight2d {
+    pub color: Color,
+    pub intensity: f32,
+    /// The direction the light travels in world space.
+    pub direction: Vec2,
+    /// How far the shadows are casted from the casters in world space.
+    pub shadow_length: f32,
+}
+
+impl Default for DirectionalLight2d {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            direction: Vec2::NEG_Y,
+            shadow_length: 100.,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct ShadowCaster2d;
 
+/// Texture used instead of the sprite texture when rendering a [`ShadowCaster2d`]
+/// sprite into shadow casters, aligned with the sprite texture.
+///
+/// These sprites are drawn after all the other sprites of the view instead of being
+/// sorted with them by z, so they end up on top of overlapping casters.
+#[derive(Component, Default, Clone, Reflect)]
+pub struct OccluderTexture2d(pub Handle<Image>);
+
+/// Makes a [`ShadowCaster2d`] sprite attenuate and tint the light passing through it
+/// by its color and opacity, instead of blocking it.
+///
+/// Directional lights are not affected.
+#[derive(Component, Default, Clone, Copy, Reflect)]
+pub struct TranslucentShadowCaster2d;
+
+/// Shape casting shadows without drawing anything, regardless of sprite alpha.
+///
+/// Defined in the local space of the `GlobalTransform` of the entity.
+#[derive(Component, Clone, Reflect)]
+pub enum ShadowCasterShape2d {
+    Rectangle {
+        half_size: Vec2,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// Two half circles connected along the y axis.
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+    ConvexPolygon(Vec<Vec2>),
+    /// Simple polygon without holes.
+    Polygon(Vec<Vec2>),
+}
+
+impl ShadowCasterShape2d {
+    pub const CIRCLE_SEGMENTS: u32 = 32;
+
+    /// Closed outline in local space.
+    pub fn outline(&self) -> Vec<Vec2> {
+        let arc = |center: Vec2, radius: f32, start: f32, segments: u32| {
+            (0..=segments).map(move |i| {
+                center
+                    + Vec2::from_angle(
+                        start + std::f32::consts::TAU * i as f32 / Self::CIRCLE_SEGMENTS as f32,
+                    ) * radius
+            })
+        };
+
+        match self {
+            Self::Rectangle { half_size } => vec![
+                -*half_size,
+                Vec2::new(half_size.x, -half_size.y),
+                *half_size,
+                Vec2::new(-half_size.x, half_size.y),
+            ],
+            Self::Circle { radius } => {
+                arc(Vec2::ZERO, *radius, 0., Self::CIRCLE_SEGMENTS - 1).collect()
+            }
+            Self::Capsule {
+                half_length,
+                radius,
+            } => {
+                let half_segments = Self::CIRCLE_SEGMENTS / 2;
+                arc(Vec2::Y * *half_length, *radius, 0., half_segments)
+                    .chain(arc(
+                        Vec2::NEG_Y * *half_length,
+                        *radius,
+                        std::f32::consts::PI,
+                        half_segments,
+                    ))
+                    .collect()
+            }
+            Self::ConvexPolygon(vertices) | Self::Polygon(vertices) => vertices.clone(),
+        }
+    }
+
+    /// Triangle list in local space.
+    pub fn triangulate(&self) -> Vec<Vec2> {
+        match self {
+            Self::Polygon(vertices) => triangulate_polygon(vertices),
+            _ => triangulate_convex(&self.outline()),
+        }
+    }
+}
+
+/// Normal map of a sprite, aligned with the sprite texture. Green points upwards.
+///
+/// Shaded by point and spot lights according to their `height`,
+/// sprites without normal maps are lit as usual.
+///
+/// Normals are stored as is, so load the image with `is_srgb` set to `false`
+/// in its `ImageLoaderSettings`.
+#[derive(Component, Default, Clone, Reflect)]
+pub struct NormalMap2d(pub Handle<Image>);
+
+/// Specular map of a sprite, aligned with the sprite texture.
+///
+/// Red channel is the specular intensity and green channel is the glossiness.
+/// Highlights are seen from right above the sprites, so they also depend on
+/// the `height` of lights.
+///
+/// The channels are read as is, so load the image with `is_srgb` set to `false`
+/// in its `ImageLoaderSettings`.
+#[derive(Component, Default, Clone, Reflect)]
+pub struct SpecularMap2d(pub Handle<Image>);
+
+/// Light emitted by a sprite itself, added after the [`AmbientLight2d`] so it stays
+/// visible in the dark.
+///
+/// `color` is multiplied by `texture` if any. Values above 1 feed bloom when the camera
+/// is HDR.
+///
+/// With `radiance_cascades`, emissive sprites also light up their surroundings, whether
+/// they are [`ShadowCaster2d`]s or not. The sprite color of casters is clamped to 1
+/// when bounced, only the emission keeps higher values.
+#[derive(Component, Clone, Reflect)]
+pub struct Emissive2d {
+    pub color: Color,
+    pub intensity: f32,
+    pub texture: Option<Handle<Image>>,
+}
+
+impl Default for Emissive2d {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            texture: None,
+        }
+    }
+}
+
+/// Sprites with this component are neither darkened by the [`AmbientLight2d`]
+/// nor lit by lights.
+///
+/// Like [`LightReceiver2d`], this is written into a mask by the surface pass,
+/// next to the [`NormalMap2d`]s, so that every approach can read it,
+/// not only the alpha map of `catalinzz`.
+#[derive(Component, Default, Clone, Copy, Reflect)]
+pub struct Unlit2d;
+
+/// Scales how much a sprite is affected by the [`AmbientLight2d`] and lights.
+///
+/// 0 is the same as [`Unlit2d`], 1 is lit as usual. Values above 1 only scale the lights,
+/// the ambient light is applied as usual.
+#[derive(Component, Clone, Copy, Reflect)]
+pub struct LightReceiver2d {
+    pub multiplier: f32,
+}
+
+impl Default for LightReceiver2d {
+    fn default() -> Self {
+        Self { multiplier: 1. }
+    }
+}
+
 #[derive(Resource, ExtractResource, Clone, Copy, Reflect)]
 #[reflect(Resource)]
 pub struct AmbientLight2d {
@@ -98,6 +438,26 @@ pub struct SpotLight2dBundle {
     pub inherited_visibility: InheritedVisibility,
 }
 
+#[derive(Bundle, Default)]
+pub struct LineLight2dBundle {
+    pub line_light: LineLight2d,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub view_visibility: ViewVisibility,
+    pub inherited_visibility: InheritedVisibility,
+}
+
+#[derive(Bundle, Default)]
+pub struct DirectionalLight2dBundle {
+    pub directional_light: DirectionalLight2d,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub view_visibility: ViewVisibility,
+    pub inherited_visibility: InheritedVisibility,
+}
+
 #[derive(Bundle)]
 pub struct ShadowCaster2dBundle {
     pub shadow_caster: ShadowCaster2d,
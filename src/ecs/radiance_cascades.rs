@@ -0,0 +1,35 @@
+use bevy::{
+    ecs::{component::Component, reflect::ReflectResource, system::Resource},
+    reflect::Reflect,
+    render::extract_resource::ExtractResource,
+};
+
+#[derive(Resource, ExtractResource, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+pub struct RadianceCascadesConfig {
+    /// The length in pixels of the rays cast by the first cascade, every following cascade
+    /// casts rays 4 times as long. Shorter intervals are more accurate near casters.
+    pub interval: f32,
+    /// How strong the global illumination is compared to direct lighting.
+    pub intensity: f32,
+    /// How much of the light received by casters is bounced off, tinted by their color.
+    /// Should be less than 1.
+    pub bounce: f32,
+}
+
+impl Default for RadianceCascadesConfig {
+    fn default() -> Self {
+        Self {
+            interval: 4.,
+            intensity: 1.,
+            bounce: 0.5,
+        }
+    }
+}
+
+/// Adds global illumination to a camera using [`Lighting2dApproach::RayMarching`],
+/// configured by [`RadianceCascadesConfig`]. Can be added or removed at any time.
+///
+/// [`Lighting2dApproach::RayMarching`]: super::Lighting2dApproach::RayMarching
+#[derive(Component, Default, Clone, Copy, Reflect)]
+pub struct RadianceCascades2d;
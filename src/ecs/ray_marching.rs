@@ -10,6 +10,15 @@ pub struct RayMarchingConfig {
     pub scale: f32,
     pub alpha_threshold: f32,
     pub edge_lighting: f32,
+    /// How many points on the segment of a line light are sampled to produce penumbra.
+    pub line_light_samples: u32,
+    /// How soft the shadows of point and spot lights are, relative to the radius of the light.
+    /// 0 gives hard shadows.
+    pub softness: f32,
+    /// The step in pixels when marching through
+    /// [`TranslucentShadowCaster2d`](crate::ecs::TranslucentShadowCaster2d)s,
+    /// to find where the light leaves them. Smaller steps are slower but more accurate.
+    pub translucent_step: f32,
 }
 
 impl Default for RayMarchingConfig {
@@ -18,6 +27,9 @@ impl Default for RayMarchingConfig {
             scale: 1.,
             alpha_threshold: 0.9,
             edge_lighting: 5.,
+            line_light_samples: 8,
+            softness: 1.,
+            translucent_step: 4.,
         }
     }
 }
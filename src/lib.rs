@@ -8,7 +8,7 @@ pub mod ecs;
 pub mod math;
 pub mod render;
 
-#[cfg(not(any(feature = "catalinzz", feature = "ray_marching")))]
+#[cfg(not(any(feature = "catalinzz", feature = "ray_marching", feature = "geometry")))]
 compile_error!("Incandescent requires at least one render approach feature to be enabled!");
 
 pub struct IncandescentPlugin;
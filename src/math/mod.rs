@@ -1,4 +1,4 @@
-use bevy::reflect::Reflect;
+use bevy::{math::Vec2, reflect::Reflect};
 
 #[derive(Clone, Copy, Reflect)]
 pub enum CircularSector {
@@ -23,10 +23,283 @@ impl CircularSector {
         }
     }
 
+    pub fn rotated(self, angle: f32) -> Self {
+        match self {
+            Self::Extent { origin, extent } => Self::Extent {
+                origin: origin + angle,
+                extent,
+            },
+            Self::Angles { start, end } => Self::Angles {
+                start: start + angle,
+                end: end + angle,
+            },
+        }
+    }
+
     pub fn into_angles(self) -> [f32; 2] {
         match self {
             Self::Extent { origin, extent } => [origin - extent, origin + extent],
             Self::Angles { start, end } => [start, end],
         }
     }
+
+    /// Angle between the direction and the bisector of the sector.
+    pub fn angle_to(self, direction: Vec2) -> f32 {
+        let [origin, _] = self.into_extent();
+        Vec2::from_angle(origin).angle_between(direction).abs()
+    }
+
+    pub fn contains(self, direction: Vec2) -> bool {
+        let [_, extent] = self.into_extent();
+        extent >= std::f32::consts::PI || self.angle_to(direction) <= extent
+    }
+
+    /// Returns 1 inside the inner sector, fading out smoothly to 0 at the edge of this sector.
+    /// The same as `get_sector_falloff` in shaders.
+    pub fn angular_falloff(self, direction: Vec2, inner_extent: f32) -> f32 {
+        let [_, extent] = self.into_extent();
+        if inner_extent >= extent {
+            return if self.contains(direction) { 1. } else { 0. };
+        }
+
+        let t = ((self.angle_to(direction) - inner_extent) / (extent - inner_extent)).clamp(0., 1.);
+        1. - t * t * (3. - 2. * t)
+    }
+}
+
+/// Triangulates a convex polygon as a fan, returning a triangle list.
+pub fn triangulate_convex(vertices: &[Vec2]) -> Vec<Vec2> {
+    if vertices.len() < 3 {
+        return Vec::new();
+    }
+
+    (1..vertices.len() - 1)
+        .flat_map(|i| [vertices[0], vertices[i], vertices[i + 1]])
+        .collect()
+}
+
+/// Removes repeated vertices and vertices lying on the line through their neighbors,
+/// which can never be clipped as ears.
+pub fn remove_degenerate_vertices(vertices: &[Vec2]) -> Vec<Vec2> {
+    let mut vertices = vertices.to_vec();
+    let mut i = 0;
+    // Vertices checked in a row since the last removal.
+    let mut checked = 0;
+    while vertices.len() >= 3 && checked < vertices.len() {
+        let len = vertices.len();
+        i %= len;
+        let to_curr = vertices[i] - vertices[(i + len - 1) % len];
+        let to_next = vertices[(i + 1) % len] - vertices[i];
+
+        let is_degenerate = to_curr.length_squared() <= f32::EPSILON
+            || to_curr.perp_dot(to_next).abs()
+                <= f32::EPSILON * to_curr.length() * to_next.length();
+        if is_degenerate {
+            vertices.remove(i);
+            checked = 0;
+        } else {
+            i += 1;
+            checked += 1;
+        }
+    }
+
+    if vertices.len() < 3 {
+        vertices.clear();
+    }
+    vertices
+}
+
+/// Triangulates a simple polygon (concave allowed, no holes) by ear clipping,
+/// returning a triangle list. Works for both windings.
+pub fn triangulate_polygon(vertices: &[Vec2]) -> Vec<Vec2> {
+    let vertices = remove_degenerate_vertices(vertices);
+    if vertices.len() < 3 {
+        return Vec::new();
+    }
+
+    let signed_area = (0..vertices.len())
+        .map(|i| vertices[i].perp_dot(vertices[(i + 1) % vertices.len()]))
+        .sum::<f32>();
+    let mut indices = (0..vertices.len()).collect::<Vec<_>>();
+    if signed_area < 0. {
+        indices.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity((vertices.len() - 2) * 3);
+    let mut i = 0;
+    let mut attempts = 0;
+    while indices.len() > 3 {
+        let len = indices.len();
+        i %= len;
+        let (prev, curr, next) = (
+            indices[(i + len - 1) % len],
+            indices[i],
+            indices[(i + 1) % len],
+        );
+        let (a, b, c) = (vertices[prev], vertices[curr], vertices[next]);
+
+        let is_ear = (b - a).perp_dot(c - b) > 0.
+            && indices
+                .iter()
+                .filter(|j| ![prev, curr, next].contains(j))
+                .all(|j| !is_point_inside_triangle(vertices[*j], a, b, c));
+
+        if is_ear {
+            triangles.extend([a, b, c]);
+            indices.remove(i);
+            attempts = 0;
+        } else {
+            i += 1;
+            attempts += 1;
+            // Self intersecting or degenerated polygons.
+            if attempts > len {
+                break;
+            }
+        }
+    }
+
+    if indices.len() == 3 {
+        triangles.extend(indices.iter().map(|i| vertices[*i]));
+    }
+    triangles
+}
+
+/// Points on the edges count as inside. `a`, `b` and `c` are counter-clockwise.
+pub fn is_point_inside_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.
+        && (c - b).perp_dot(point - b) >= 0.
+        && (a - c).perp_dot(point - c) >= 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    /// Pointing upwards, 45 degrees to each side.
+    fn upper_sector() -> CircularSector {
+        CircularSector::Extent {
+            origin: FRAC_PI_2,
+            extent: FRAC_PI_4,
+        }
+    }
+
+    #[test]
+    fn sector_angle_to() {
+        let sector = upper_sector();
+        assert!(sector.angle_to(Vec2::Y).abs() < EPSILON);
+        assert!((sector.angle_to(Vec2::X) - FRAC_PI_2).abs() < EPSILON);
+        assert!((sector.angle_to(Vec2::NEG_X) - FRAC_PI_2).abs() < EPSILON);
+        assert!((sector.angle_to(Vec2::NEG_Y) - PI).abs() < EPSILON);
+        // Both forms describe the same sector.
+        let angles = CircularSector::Angles {
+            start: FRAC_PI_4,
+            end: FRAC_PI_4 * 3.,
+        };
+        assert!((angles.angle_to(Vec2::ONE) - sector.angle_to(Vec2::ONE)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn sector_contains() {
+        let sector = upper_sector();
+        assert!(sector.contains(Vec2::Y));
+        assert!(sector.contains(Vec2::new(0.5, 1.)));
+        assert!(!sector.contains(Vec2::X));
+        assert!(!sector.contains(Vec2::NEG_Y));
+        assert!(CircularSector::default().contains(Vec2::NEG_Y));
+    }
+
+    #[test]
+    fn sector_angular_falloff() {
+        let sector = upper_sector();
+        let inner_extent = FRAC_PI_4 / 2.;
+        assert_eq!(sector.angular_falloff(Vec2::Y, inner_extent), 1.);
+        assert_eq!(sector.angular_falloff(Vec2::X, inner_extent), 0.);
+        let halfway = Vec2::from_angle(FRAC_PI_2 - FRAC_PI_4 * 0.75);
+        assert!((sector.angular_falloff(halfway, inner_extent) - 0.5).abs() < EPSILON);
+        // Hard edges without an inner cone.
+        assert_eq!(sector.angular_falloff(Vec2::ONE, FRAC_PI_4), 1.);
+        assert_eq!(sector.angular_falloff(Vec2::X, FRAC_PI_4), 0.);
+    }
+
+    #[test]
+    fn sector_rotated() {
+        let [origin, extent] = upper_sector().rotated(FRAC_PI_2).into_extent();
+        assert!((origin - PI).abs() < EPSILON);
+        assert!((extent - FRAC_PI_4).abs() < EPSILON);
+        assert!(upper_sector().rotated(FRAC_PI_2).contains(Vec2::NEG_X));
+
+        let [start, end] = CircularSector::Angles { start: 0., end: 1. }
+            .rotated(-1.)
+            .into_angles();
+        assert_eq!([start, end], [-1., 0.]);
+    }
+
+    /// Asserts that `triangles` are counter-clockwise and cover `area`.
+    fn assert_triangles(triangles: &[Vec2], count: usize, area: f32) {
+        assert_eq!(triangles.len(), count * 3);
+        let mut total = 0.;
+        for triangle in triangles.chunks_exact(3) {
+            let doubled = (triangle[1] - triangle[0]).perp_dot(triangle[2] - triangle[0]);
+            assert!(doubled > 0., "{:?} isn't counter-clockwise", triangle);
+            total += doubled / 2.;
+        }
+        assert!((total - area).abs() < 1e-4, "area {} != {}", total, area);
+    }
+
+    fn square() -> Vec<Vec2> {
+        vec![
+            Vec2::new(0., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 2.),
+            Vec2::new(0., 2.),
+        ]
+    }
+
+    #[test]
+    fn triangulate_convex_polygon() {
+        assert_triangles(&triangulate_polygon(&square()), 2, 4.);
+    }
+
+    #[test]
+    fn triangulate_concave_polygon() {
+        let l_shape = [
+            Vec2::new(0., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 1.),
+            Vec2::new(1., 1.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+        ];
+        assert_triangles(&triangulate_polygon(&l_shape), 4, 3.);
+    }
+
+    #[test]
+    fn triangulate_collinear_vertices() {
+        let vertices = [
+            Vec2::new(0., 0.),
+            Vec2::new(1., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 2.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+            Vec2::new(0., 1.),
+        ];
+        assert_eq!(remove_degenerate_vertices(&vertices), square());
+        assert_triangles(&triangulate_polygon(&vertices), 2, 4.);
+
+        let line = [Vec2::ZERO, Vec2::X, Vec2::X * 2.];
+        assert!(triangulate_polygon(&line).is_empty());
+    }
+
+    #[test]
+    fn triangulate_clockwise_polygon() {
+        let mut vertices = square();
+        vertices.reverse();
+        assert_triangles(&triangulate_polygon(&vertices), 2, 4.);
+    }
 }
@@ -0,0 +1,668 @@
+use bevy::{
+    asset::AssetId,
+    core_pipeline::core_2d::{Camera2d, Transparent2d},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Has, Or, With, Without},
+        system::{Commands, Query, Res, ResMut, Resource, SystemParam},
+        world::{FromWorld, World},
+    },
+    math::{Mat4, UVec2, UVec4, Vec2, Vec3, Vec4},
+    render::{
+        camera::Camera,
+        color::Color,
+        render_asset::RenderAssets,
+        render_phase::{RenderPhase, TrackedRenderPass},
+        render_resource::{
+            AddressMode, BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            BlendComponent, BlendFactor, BlendOperation, BlendState, BufferUsages, BufferVec,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, DynamicUniformBuffer, Extent3d,
+            FilterMode, FragmentState, MultisampleState, PipelineCache, PrimitiveState,
+            RenderPassDescriptor, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, ShaderType, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureSampleType, TextureUsages, VertexBufferLayout, VertexFormat,
+            VertexState, VertexStepMode,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{BevyDefault, ColorAttachment, Image, TextureCache},
+        view::{
+            ColorGrading, ExtractedView, InheritedVisibility, ViewTarget, ViewUniform,
+            ViewUniformOffset, ViewUniforms, VisibleEntities,
+        },
+        Extract,
+    },
+    sprite::ExtractedSprites,
+    transform::components::GlobalTransform,
+    utils::HashMap,
+};
+
+use bevy::render::render_resource::binding_types as binding;
+
+use crate::ecs::{
+    Lighting2dApproach, OccluderTexture2d, ShadowCaster2d, ShadowCasterShape2d,
+    TranslucentShadowCaster2d,
+};
+
+use super::{surface::compute_sprite_quad, CASTER_SHAPE_SHADER, OCCLUDER_SHADER};
+
+/// Triangle list of a [`ShadowCasterShape2d`] in local space,
+/// only recomputed when the shape changes.
+#[derive(Component)]
+pub struct CasterShapeTriangles2d(pub Vec<Vec2>);
+
+pub fn triangulate_caster_shapes(
+    mut commands: Commands,
+    shapes_query: Query<(Entity, &ShadowCasterShape2d), Changed<ShadowCasterShape2d>>,
+) {
+    for (entity, shape) in &shapes_query {
+        commands
+            .entity(entity)
+            .insert(CasterShapeTriangles2d(shape.triangulate()));
+    }
+}
+
+#[derive(Component)]
+pub struct ExtractedCasterShape2d {
+    /// Triangle list in world space.
+    pub triangles: Vec<Vec3>,
+}
+
+/// A [`ShadowCaster2d`] sprite that isn't rendered through the [`Transparent2d`] phase.
+///
+/// [`Transparent2d`]: bevy::core_pipeline::core_2d::Transparent2d
+#[derive(Component, Clone, Copy)]
+pub struct ExtractedOccluder2d {
+    /// Used instead of the sprite texture if present.
+    pub texture: Option<AssetId<Image>>,
+    pub translucent: bool,
+}
+
+type ExtractCasterShape = (
+    Entity,
+    &'static CasterShapeTriangles2d,
+    &'static GlobalTransform,
+    Option<&'static InheritedVisibility>,
+);
+
+type ExtractOccluder = (
+    Entity,
+    Option<&'static OccluderTexture2d>,
+    Has<TranslucentShadowCaster2d>,
+);
+
+type OccluderFilter = (
+    With<ShadowCaster2d>,
+    Or<(With<OccluderTexture2d>, With<TranslucentShadowCaster2d>)>,
+);
+
+pub fn extract_casters(
+    mut commands: Commands,
+    shapes_query: Extract<Query<ExtractCasterShape, With<ShadowCasterShape2d>>>,
+    occluders_query: Extract<Query<ExtractOccluder, OccluderFilter>>,
+) {
+    commands.insert_or_spawn_batch(
+        shapes_query
+            .iter()
+            .filter(|(.., visibility)| {
+                visibility
+                    .copied()
+                    .unwrap_or(InheritedVisibility::VISIBLE)
+                    .get()
+            })
+            .map(|(entity, triangles, transform, _)| {
+                (
+                    entity,
+                    ExtractedCasterShape2d {
+                        triangles: triangles
+                            .0
+                            .iter()
+                            .map(|vertex| transform.transform_point(vertex.extend(0.)))
+                            .collect(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    commands.insert_or_spawn_batch(
+        occluders_query
+            .iter()
+            .map(|(entity, texture, translucent)| {
+                (
+                    entity,
+                    ExtractedOccluder2d {
+                        texture: texture.map(|texture| texture.0.id()),
+                        translucent,
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+/// The view rendering the [`ShadowCaster2d`] sprites of a camera, apart from the other
+/// sprites of its [`Transparent2d`] phase. Inserted on the main view.
+#[derive(Component)]
+pub struct CasterView2d(pub Entity);
+
+type ExtractCasterCamera = (
+    Entity,
+    &'static Camera,
+    &'static GlobalTransform,
+    &'static VisibleEntities,
+    Option<&'static Lighting2dApproach>,
+);
+
+type PhaseCasterFilter = (
+    With<ShadowCaster2d>,
+    Without<OccluderTexture2d>,
+    Without<TranslucentShadowCaster2d>,
+);
+
+pub fn extract_caster_views(
+    mut commands: Commands,
+    cameras_query: Extract<Query<ExtractCasterCamera, With<Camera2d>>>,
+    casters_query: Extract<Query<(), PhaseCasterFilter>>,
+) {
+    for (camera_entity, camera, transform, visible_entities, approach) in &cameras_query {
+        // Geometry casts shadows from the rectangles of sprites instead.
+        if !camera.is_active
+            || !matches!(
+                approach.copied().unwrap_or_default(),
+                Lighting2dApproach::Catalinzz | Lighting2dApproach::RayMarching
+            )
+        {
+            continue;
+        }
+
+        // Sprites with occluder textures and translucent casters
+        // are rendered by `render_casters()` instead.
+        let caster_view = commands
+            .spawn((
+                ExtractedView {
+                    projection: camera.projection_matrix(),
+                    transform: *transform,
+                    view_projection: None,
+                    // Only the alpha of casters and their albedo for radiance cascades are
+                    // rendered, both within 0..1. Emission comes from the hdr surfaces.
+                    hdr: false,
+                    viewport: UVec4::ZERO,
+                    color_grading: ColorGrading::default(),
+                },
+                VisibleEntities {
+                    entities: visible_entities
+                        .iter()
+                        .copied()
+                        .filter(|entity| casters_query.contains(*entity))
+                        .collect(),
+                },
+                RenderPhase::<Transparent2d>::default(),
+            ))
+            .id();
+
+        commands
+            .get_or_spawn(camera_entity)
+            .insert(CasterView2d(caster_view));
+    }
+}
+
+#[derive(ShaderType)]
+pub struct GpuOccluder2d {
+    pub model: Mat4,
+    /// Offset in xy and scale in zw.
+    pub uv_rect: Vec4,
+    pub color: Vec4,
+}
+
+pub const TRANSMITTANCE_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// The target [`TranslucentShadowCaster2d`]s of a view are drawn into, cleared to white.
+///
+/// Kept apart from the target of the other casters, so the blended edges of opaque
+/// sprites aren't mistaken for translucent casters.
+#[derive(Component)]
+pub struct TransmittanceView2d {
+    pub attachment: ColorAttachment,
+}
+
+impl TransmittanceView2d {
+    pub fn new(
+        size: UVec2,
+        texture_cache: &mut TextureCache,
+        render_device: &RenderDevice,
+    ) -> Self {
+        let texture = texture_cache.get(
+            render_device,
+            TextureDescriptor {
+                label: Some("transmittance_view_texture"),
+                size: Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TRANSMITTANCE_TARGET_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+
+        Self {
+            attachment: ColorAttachment::new(texture, None, Some(Color::WHITE)),
+        }
+    }
+}
+
+/// Draws [`ShadowCasterShape2d`]s and sprites with [`OccluderTexture2d`]s into the
+/// targets of shadow casters, which have the format of the main texture. The `hdr_`
+/// pipelines are used for targets sharing the render phase of hdr cameras.
+///
+/// [`TranslucentShadowCaster2d`]s multiply their transmittance into the
+/// [`TransmittanceView2d`] instead.
+#[derive(Resource)]
+pub struct Caster2dPipeline {
+    pub shape_pipeline_id: CachedRenderPipelineId,
+    pub occluder_pipeline_id: CachedRenderPipelineId,
+    pub translucent_pipeline_id: CachedRenderPipelineId,
+    pub hdr_shape_pipeline_id: CachedRenderPipelineId,
+    pub hdr_occluder_pipeline_id: CachedRenderPipelineId,
+    pub view_layout: BindGroupLayout,
+    pub occluder_texture_layout: BindGroupLayout,
+    pub occluder_layout: BindGroupLayout,
+    pub sampler: Sampler,
+}
+
+impl FromWorld for Caster2dPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let view_layout = render_device.create_bind_group_layout(
+            "caster_2d_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                binding::uniform_buffer::<ViewUniform>(true),
+            ),
+        );
+
+        let occluder_texture_layout = render_device.create_bind_group_layout(
+            "occluder_2d_texture_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let occluder_layout = render_device.create_bind_group_layout(
+            "occluder_2d_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                binding::uniform_buffer::<GpuOccluder2d>(true),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("occluder_2d_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let shape_pipeline_descriptor =
+            |label: &str, target: ColorTargetState| RenderPipelineDescriptor {
+                label: Some(label.to_string().into()),
+                layout: vec![view_layout.clone()],
+                push_constant_ranges: vec![],
+                vertex: VertexState {
+                    shader: CASTER_SHAPE_SHADER,
+                    shader_defs: vec![],
+                    entry_point: "vertex".into(),
+                    buffers: vec![VertexBufferLayout::from_vertex_formats(
+                        VertexStepMode::Vertex,
+                        [VertexFormat::Float32x3],
+                    )],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    shader: CASTER_SHAPE_SHADER,
+                    shader_defs: vec![],
+                    entry_point: "fragment".into(),
+                    targets: vec![Some(target)],
+                }),
+            };
+
+        let occluder_pipeline_descriptor =
+            |label: &str, entry_point: &'static str, target: ColorTargetState| {
+                RenderPipelineDescriptor {
+                    label: Some(label.to_string().into()),
+                    layout: vec![
+                        view_layout.clone(),
+                        occluder_texture_layout.clone(),
+                        occluder_layout.clone(),
+                    ],
+                    push_constant_ranges: vec![],
+                    vertex: VertexState {
+                        shader: OCCLUDER_SHADER,
+                        shader_defs: vec![],
+                        entry_point: "vertex".into(),
+                        buffers: vec![],
+                    },
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    fragment: Some(FragmentState {
+                        shader: OCCLUDER_SHADER,
+                        shader_defs: vec![],
+                        entry_point: entry_point.into(),
+                        targets: vec![Some(target)],
+                    }),
+                }
+            };
+
+        // Returns the ids of the shape and occluder pipelines.
+        let queue_pipelines = |format: TextureFormat, label_suffix: &str| {
+            let target = ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            };
+
+            (
+                pipeline_cache.queue_render_pipeline(shape_pipeline_descriptor(
+                    &format!("caster_shape_2d{label_suffix}_pipeline"),
+                    target.clone(),
+                )),
+                pipeline_cache.queue_render_pipeline(occluder_pipeline_descriptor(
+                    &format!("occluder_2d{label_suffix}_pipeline"),
+                    "fragment",
+                    target,
+                )),
+            )
+        };
+
+        let (shape_pipeline_id, occluder_pipeline_id) =
+            queue_pipelines(TextureFormat::bevy_default(), "");
+        let (hdr_shape_pipeline_id, hdr_occluder_pipeline_id) =
+            queue_pipelines(ViewTarget::TEXTURE_FORMAT_HDR, "_hdr");
+
+        // Multiplies the transmittance into the target.
+        let translucent_pipeline_id =
+            pipeline_cache.queue_render_pipeline(occluder_pipeline_descriptor(
+                "translucent_occluder_2d_pipeline",
+                "translucent_fragment",
+                ColorTargetState {
+                    format: TRANSMITTANCE_TARGET_FORMAT,
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::Dst,
+                            dst_factor: BlendFactor::Zero,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                    write_mask: ColorWrites::COLOR,
+                },
+            ));
+
+        Self {
+            shape_pipeline_id,
+            occluder_pipeline_id,
+            translucent_pipeline_id,
+            hdr_shape_pipeline_id,
+            hdr_occluder_pipeline_id,
+            view_layout,
+            occluder_texture_layout,
+            occluder_layout,
+            sampler,
+        }
+    }
+}
+
+pub struct OccluderDraw2d {
+    pub texture_bind_group: BindGroup,
+    pub dynamic_offset: u32,
+}
+
+#[derive(Resource)]
+pub struct Caster2dBuffers {
+    shape_vertices: BufferVec<Vec3>,
+    occluders: DynamicUniformBuffer<GpuOccluder2d>,
+    occluder_draws: Vec<OccluderDraw2d>,
+    translucent_draws: Vec<OccluderDraw2d>,
+    occluder_bind_group: Option<BindGroup>,
+    view_bind_group: Option<BindGroup>,
+}
+
+impl Default for Caster2dBuffers {
+    fn default() -> Self {
+        Self {
+            shape_vertices: BufferVec::new(BufferUsages::VERTEX),
+            occluders: Default::default(),
+            occluder_draws: Vec::new(),
+            translucent_draws: Vec::new(),
+            occluder_bind_group: None,
+            view_bind_group: None,
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub struct CasterResources<'w> {
+    images: Res<'w, RenderAssets<Image>>,
+    pipeline: Res<'w, Caster2dPipeline>,
+    view_uniforms: Res<'w, ViewUniforms>,
+    render_device: Res<'w, RenderDevice>,
+    render_queue: Res<'w, RenderQueue>,
+}
+
+pub fn prepare_casters(
+    shapes_query: Query<&ExtractedCasterShape2d>,
+    occluders_query: Query<&ExtractedOccluder2d>,
+    extracted_sprites: Res<ExtractedSprites>,
+    mut buffers: ResMut<Caster2dBuffers>,
+    resources: CasterResources,
+) {
+    let CasterResources {
+        images,
+        pipeline,
+        view_uniforms,
+        render_device,
+        render_queue,
+    } = resources;
+    let buffers = &mut *buffers;
+
+    buffers.shape_vertices.clear();
+    for shape in &shapes_query {
+        for vertex in &shape.triangles {
+            buffers.shape_vertices.push(*vertex);
+        }
+    }
+    buffers
+        .shape_vertices
+        .write_buffer(&render_device, &render_queue);
+
+    buffers.occluders.clear();
+    buffers.occluder_draws.clear();
+    buffers.translucent_draws.clear();
+
+    let mut sprites = extracted_sprites.sprites.iter().collect::<Vec<_>>();
+    radsort::sort_by_key(&mut sprites, |(_, sprite)| sprite.transform.translation().z);
+
+    let mut texture_bind_groups = HashMap::new();
+    for (entity, sprite) in sprites {
+        let Ok(occluder) = occluders_query.get(sprite.original_entity.unwrap_or(*entity)) else {
+            continue;
+        };
+        let texture = occluder.texture.unwrap_or(sprite.image_handle_id);
+        let (Some(sprite_image), Some(occluder_image)) =
+            (images.get(sprite.image_handle_id), images.get(texture))
+        else {
+            continue;
+        };
+
+        let texture_bind_group = texture_bind_groups
+            .entry(texture)
+            .or_insert_with(|| {
+                render_device.create_bind_group(
+                    "occluder_2d_texture_bind_group",
+                    &pipeline.occluder_texture_layout,
+                    &BindGroupEntries::sequential((
+                        &occluder_image.texture_view,
+                        &pipeline.sampler,
+                    )),
+                )
+            })
+            .clone();
+
+        // The occluder texture is aligned with the sprite texture.
+        let (model, uv_rect, _) = compute_sprite_quad(sprite, sprite_image.size);
+        let dynamic_offset = buffers.occluders.push(&GpuOccluder2d {
+            model,
+            uv_rect,
+            color: Vec4::from_array(sprite.color.as_linear_rgba_f32()),
+        });
+
+        let draw = OccluderDraw2d {
+            texture_bind_group,
+            dynamic_offset,
+        };
+        if occluder.translucent {
+            buffers.translucent_draws.push(draw);
+        } else {
+            buffers.occluder_draws.push(draw);
+        }
+    }
+
+    buffers
+        .occluders
+        .write_buffer(&render_device, &render_queue);
+    buffers.occluder_bind_group = buffers.occluders.binding().map(|binding| {
+        render_device.create_bind_group(
+            "occluder_2d_bind_group",
+            &pipeline.occluder_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+
+    buffers.view_bind_group = view_uniforms.uniforms.binding().map(|binding| {
+        render_device.create_bind_group(
+            "caster_2d_view_bind_group",
+            &pipeline.view_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+}
+
+/// Draws all caster shapes and sprites with occluder textures in the view,
+/// after the sprites in the same pass.
+///
+/// They aren't queued into the [`Transparent2d`] phase, so they are only sorted by z
+/// among themselves and always cover the sprites of the phase.
+///
+/// [`Transparent2d`]: bevy::core_pipeline::core_2d::Transparent2d
+pub fn render_casters<'w>(
+    render_pass: &mut TrackedRenderPass<'w>,
+    world: &'w World,
+    view_offset: &ViewUniformOffset,
+    hdr: bool,
+) {
+    let buffers = world.resource::<Caster2dBuffers>();
+    let pipeline = world.resource::<Caster2dPipeline>();
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let Some(view_bind_group) = buffers.view_bind_group.as_ref() else {
+        return;
+    };
+    let (shape_pipeline_id, occluder_pipeline_id) = if hdr {
+        (
+            pipeline.hdr_shape_pipeline_id,
+            pipeline.hdr_occluder_pipeline_id,
+        )
+    } else {
+        (pipeline.shape_pipeline_id, pipeline.occluder_pipeline_id)
+    };
+
+    render_occluders(
+        render_pass,
+        world,
+        occluder_pipeline_id,
+        &buffers.occluder_draws,
+        view_bind_group,
+        view_offset,
+    );
+
+    if let (Some(render_pipeline), Some(vertices)) = (
+        pipeline_cache.get_render_pipeline(shape_pipeline_id),
+        buffers.shape_vertices.buffer(),
+    ) {
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, view_bind_group, &[view_offset.offset]);
+        render_pass.set_vertex_buffer(0, vertices.slice(..));
+        render_pass.draw(0..buffers.shape_vertices.len() as u32, 0..1);
+    }
+}
+
+/// Draws the translucent casters in the view into its [`TransmittanceView2d`].
+pub fn render_translucent_casters<'w>(
+    render_context: &mut RenderContext<'w>,
+    world: &'w World,
+    transmittance_view: &TransmittanceView2d,
+    view_offset: &ViewUniformOffset,
+) {
+    let buffers = world.resource::<Caster2dBuffers>();
+    let Some(view_bind_group) = buffers.view_bind_group.as_ref() else {
+        return;
+    };
+
+    let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+        label: Some("translucent_caster_2d_pass"),
+        color_attachments: &[Some(transmittance_view.attachment.get_attachment())],
+        ..Default::default()
+    });
+
+    render_occluders(
+        &mut render_pass,
+        world,
+        world.resource::<Caster2dPipeline>().translucent_pipeline_id,
+        &buffers.translucent_draws,
+        view_bind_group,
+        view_offset,
+    );
+}
+
+fn render_occluders<'w>(
+    render_pass: &mut TrackedRenderPass<'w>,
+    world: &'w World,
+    pipeline_id: CachedRenderPipelineId,
+    draws: &'w [OccluderDraw2d],
+    view_bind_group: &'w BindGroup,
+    view_offset: &ViewUniformOffset,
+) {
+    let buffers = world.resource::<Caster2dBuffers>();
+    let (Some(render_pipeline), Some(occluder_bind_group)) = (
+        world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline_id),
+        buffers.occluder_bind_group.as_ref(),
+    ) else {
+        return;
+    };
+
+    render_pass.set_render_pipeline(render_pipeline);
+    render_pass.set_bind_group(0, view_bind_group, &[view_offset.offset]);
+    for draw in draws {
+        render_pass.set_bind_group(1, &draw.texture_bind_group, &[]);
+        render_pass.set_bind_group(2, occluder_bind_group, &[draw.dynamic_offset]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -1,10 +1,12 @@
 use bevy::{
     core_pipeline::core_2d::Transparent2d,
     ecs::{
-        query::{QueryState, With},
+        entity::Entity,
+        query::{Has, QueryState, With},
         system::lifetimeless::Read,
         world::{FromWorld, World},
     },
+    math::UVec3,
     render::{
         render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
         render_phase::RenderPhase,
@@ -13,14 +15,17 @@ use bevy::{
             RenderPassColorAttachment, RenderPassDescriptor,
         },
         renderer::RenderContext,
-        view::{ViewTarget, ViewUniformOffset, ViewUniforms, VisibleEntities},
+        view::{ViewTarget, ViewUniformOffset, ViewUniforms},
     },
 };
 
 use crate::{
     ecs::{catalinzz::MainShadowCameraDriver, ShadowView2d},
     render::{
+        caster::{render_casters, render_translucent_casters, CasterView2d, TransmittanceView2d},
+        cookie::LightCookieStorage,
         light::{GpuAmbientLight2dBuffer, GpuLights2d},
+        surface::ViewSurfaces2d,
         universal_buffers::NumberBuffer,
         DynamicUniformIndex,
     },
@@ -28,10 +33,11 @@ use crate::{
 
 use super::{
     pipeline::{
-        Shadow2dDistortPassPipeline, Shadow2dMainPassPipeline, Shadow2dPrepassPipeline,
-        Shadow2dReductionPipeline,
+        Shadow2dBlurPassPipeline, Shadow2dDistortPassPipeline, Shadow2dMainPassPipeline,
+        Shadow2dPrepassPipeline, Shadow2dReductionPipeline,
     },
-    AlphaMapAttachment, GpuMetaBuffers, GpuShadowMapMeta, PoissonDiskBuffer, ShadowMap2dStorage,
+    AlphaMapAttachment, GpuMetaBuffers, GpuShadowMapIndex, PoissonDiskBuffer, ShadowMap2dStorage,
+    ViewShadowMapMetas, MOMENT_MAP_WIDTH, SHADOW_WORKGROUP_SIZE,
 };
 
 #[derive(RenderLabel, Debug, Hash, PartialEq, Eq, Clone)]
@@ -40,25 +46,33 @@ pub enum Shadow2dNode {
     Shadow2dPrepass,
     Shadow2dDistortPass,
     Shadow2dReductionPass,
+    Shadow2dBlurPass,
     Shadow2dMainPass,
 }
 
+type MeshPassLightView = (
+    Entity,
+    Read<RenderPhase<Transparent2d>>,
+    Read<ShadowView2d>,
+    Read<TransmittanceView2d>,
+    Read<ViewUniformOffset>,
+);
+
 pub struct Shadow2dMeshPassNode {
-    main_view_query: QueryState<
-        (
-            Read<VisibleEntities>,
-            Read<AlphaMapAttachment>,
-            Read<RenderPhase<Transparent2d>>,
-        ),
-        With<MainShadowCameraDriver>,
-    >,
-    light_view_query: QueryState<(Read<RenderPhase<Transparent2d>>, Read<ShadowView2d>)>,
+    main_view_query: QueryState<(
+        Read<AlphaMapAttachment>,
+        Read<CasterView2d>,
+        Has<MainShadowCameraDriver>,
+    )>,
+    caster_view_query: QueryState<(Read<RenderPhase<Transparent2d>>, Read<ViewUniformOffset>)>,
+    light_view_query: QueryState<MeshPassLightView, With<DynamicUniformIndex<GpuShadowMapIndex>>>,
 }
 
 impl FromWorld for Shadow2dMeshPassNode {
     fn from_world(world: &mut World) -> Self {
         Self {
             main_view_query: world.query_filtered(),
+            caster_view_query: world.query_filtered(),
             light_view_query: world.query_filtered(),
         }
     }
@@ -68,6 +82,7 @@ impl Node for Shadow2dMeshPassNode {
     #[inline]
     fn update(&mut self, world: &mut World) {
         self.main_view_query.update_archetypes(world);
+        self.caster_view_query.update_archetypes(world);
         self.light_view_query.update_archetypes(world);
     }
 
@@ -77,28 +92,35 @@ impl Node for Shadow2dMeshPassNode {
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        let main_view_entity = graph.view_entity();
-        let Ok((view_lights, alpha_map_attachment, transparent_phase)) =
-            self.main_view_query.get_manual(world, main_view_entity)
+        let Ok((alpha_map_attachment, caster_view, is_driver)) =
+            self.main_view_query.get_manual(world, graph.view_entity())
+        else {
+            return Ok(());
+        };
+        let Ok((caster_phase, view_offset)) =
+            self.caster_view_query.get_manual(world, caster_view.0)
         else {
             return Ok(());
         };
 
+        // Only casters are rendered, so the other sprites are lit and don't block the light.
         let mut alpha_map_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
             label: Some("alpha_map_pass"),
             color_attachments: &[Some(alpha_map_attachment.attachment.get_attachment())],
             ..Default::default()
         });
-        transparent_phase.render(&mut alpha_map_pass, world, main_view_entity);
+        caster_phase.render(&mut alpha_map_pass, world, caster_view.0);
+        render_casters(&mut alpha_map_pass, world, view_offset, false);
         drop(alpha_map_pass);
 
-        for light_entity in view_lights.iter().copied() {
-            let Ok((transparent_phase, shadow_view)) =
-                self.light_view_query.get_manual(world, light_entity)
-            else {
-                return Ok(());
-            };
+        // Shadow maps are shared by all cameras, so only rendered once.
+        if !is_driver {
+            return Ok(());
+        }
 
+        for (light_entity, transparent_phase, shadow_view, transmittance_view, light_view_offset) in
+            self.light_view_query.iter_manual(world)
+        {
             let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
                 label: Some("light_2d_mesh_pass"),
                 color_attachments: &[Some(shadow_view.attachment.get_attachment())],
@@ -106,22 +128,36 @@ impl Node for Shadow2dMeshPassNode {
             });
 
             transparent_phase.render(&mut render_pass, world, light_entity);
+            render_casters(&mut render_pass, world, light_view_offset, false);
+            drop(render_pass);
+
+            render_translucent_casters(
+                render_context,
+                world,
+                transmittance_view,
+                light_view_offset,
+            );
         }
 
         Ok(())
     }
 }
 
+type PrepassLightView = (
+    Read<ShadowView2d>,
+    Read<TransmittanceView2d>,
+    Read<DynamicUniformIndex<GpuShadowMapIndex>>,
+);
+
 pub struct Shadow2dPrepassNode {
-    light_view_query: QueryState<(
-        Read<ShadowView2d>,
-        Read<DynamicUniformIndex<GpuShadowMapMeta>>,
-    )>,
+    main_view_query: QueryState<(), With<MainShadowCameraDriver>>,
+    light_view_query: QueryState<PrepassLightView>,
 }
 
 impl FromWorld for Shadow2dPrepassNode {
     fn from_world(world: &mut World) -> Self {
         Self {
+            main_view_query: world.query_filtered(),
             light_view_query: world.query_filtered(),
         }
     }
@@ -130,16 +166,22 @@ impl FromWorld for Shadow2dPrepassNode {
 impl Node for Shadow2dPrepassNode {
     #[inline]
     fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
         self.light_view_query.update_archetypes(world);
     }
 
     fn run<'w>(
         &self,
-        _graph: &mut RenderGraphContext,
+        graph: &mut RenderGraphContext,
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        if self.light_view_query.iter_manual(world).next().is_none() {
+        if self
+            .main_view_query
+            .get_manual(world, graph.view_entity())
+            .is_err()
+            || self.light_view_query.iter_manual(world).next().is_none()
+        {
             return Ok(());
         }
 
@@ -153,16 +195,27 @@ impl Node for Shadow2dPrepassNode {
 
         let gpu_meta_buffers = world.resource::<GpuMetaBuffers>();
         let shadow_map_storage = world.resource::<ShadowMap2dStorage>();
-        let work_group_count = shadow_map_storage.work_group_count_per_light();
 
-        for (shadow_view, uniform_index) in self.light_view_query.iter_manual(world) {
+        for (shadow_view, transmittance_view, uniform_index) in
+            self.light_view_query.iter_manual(world)
+        {
+            let shadow_map_size = shadow_view.attachment.texture.texture.width();
+            let work_group_count = UVec3 {
+                x: shadow_map_size.div_ceil(SHADOW_WORKGROUP_SIZE.x),
+                y: shadow_map_size.div_ceil(SHADOW_WORKGROUP_SIZE.y),
+                z: 1,
+            };
+
             let bind_group = render_context.render_device().create_bind_group(
                 "light_2d_prepass_bind_group",
                 &pipeline.prepass_layout,
                 &BindGroupEntries::sequential((
                     &shadow_view.attachment.texture.default_view,
                     shadow_map_storage.texture_view_primary(),
-                    gpu_meta_buffers.shadow_map_meta_buffer_binding(),
+                    gpu_meta_buffers.light_index_binding(),
+                    shadow_map_storage.transmittance_map_view_primary(),
+                    gpu_meta_buffers.shadow_map_metas_binding(),
+                    &transmittance_view.attachment.texture.default_view,
                 )),
             );
 
@@ -240,7 +293,9 @@ impl Node for Shadow2dDistortPassNode {
             &BindGroupEntries::sequential((
                 shadow_map_storage.texture_view_primary(),
                 shadow_map_storage.texture_view_secondary(),
-                gpu_meta_buffers.shadow_map_meta_buffer_binding(),
+                gpu_meta_buffers.shadow_map_metas_binding(),
+                shadow_map_storage.transmittance_map_view_primary(),
+                shadow_map_storage.transmittance_map_view_secondary(),
             )),
         );
 
@@ -318,8 +373,10 @@ impl Node for Shadow2dReductionNode {
             &BindGroupEntries::sequential((
                 shadow_map_storage.texture_view_primary(),
                 shadow_map_storage.texture_view_secondary(),
-                gpu_meta_buffers.shadow_map_meta_buffer_binding(),
+                gpu_meta_buffers.shadow_map_metas_binding(),
                 number_buffer.binding(),
+                shadow_map_storage.transmittance_map_view_primary(),
+                shadow_map_storage.transmittance_map_view_secondary(),
             )),
         );
         let bind_group_secondary_source = render_context.render_device().create_bind_group(
@@ -328,8 +385,10 @@ impl Node for Shadow2dReductionNode {
             &BindGroupEntries::sequential((
                 shadow_map_storage.texture_view_secondary(),
                 shadow_map_storage.texture_view_primary(),
-                gpu_meta_buffers.shadow_map_meta_buffer_binding(),
+                gpu_meta_buffers.shadow_map_metas_binding(),
                 number_buffer.binding(),
+                shadow_map_storage.transmittance_map_view_secondary(),
+                shadow_map_storage.transmittance_map_view_primary(),
             )),
         );
         let bind_groups = [&bind_group_secondary_source, &bind_group_primary_source];
@@ -357,21 +416,98 @@ impl Node for Shadow2dReductionNode {
     }
 }
 
+pub struct Shadow2dBlurPassNode {
+    main_view_query: QueryState<(), With<MainShadowCameraDriver>>,
+    light_view_query: QueryState<(), With<ShadowView2d>>,
+}
+
+impl FromWorld for Shadow2dBlurPassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+            light_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for Shadow2dBlurPassNode {
+    #[inline]
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+        self.light_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok(_) = self.main_view_query.get_manual(world, graph.view_entity()) else {
+            return Ok(());
+        };
+
+        if self.light_view_query.iter_manual(world).next().is_none() {
+            return Ok(());
+        }
+
+        let pipeline = world.resource::<Shadow2dBlurPassPipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.cached_id)
+        else {
+            return Ok(());
+        };
+
+        let gpu_meta_buffers = world.resource::<GpuMetaBuffers>();
+        let shadow_map_storage = world.resource::<ShadowMap2dStorage>();
+        let work_group_count = shadow_map_storage.work_group_count_total();
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "light_2d_blur_pass_bind_group",
+            &pipeline.blur_pass_layout,
+            &BindGroupEntries::sequential((
+                shadow_map_storage.final_texture_view(),
+                shadow_map_storage.moment_map_view(),
+                gpu_meta_buffers.shadow_map_metas_binding(),
+            )),
+        );
+
+        let mut compute_pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("light_2d_blur_pass"),
+                    timestamp_writes: None,
+                });
+
+        compute_pass.set_pipeline(compute_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            MOMENT_MAP_WIDTH.div_ceil(SHADOW_WORKGROUP_SIZE.x),
+            work_group_count.y,
+            work_group_count.z,
+        );
+
+        Ok(())
+    }
+}
+
 pub struct Shadow2dMainPassNode {
     main_view_query: QueryState<(
         Read<ViewTarget>,
         Read<ViewUniformOffset>,
         Read<GpuLights2d>,
         Read<AlphaMapAttachment>,
+        Read<ViewSurfaces2d>,
+        Read<ViewShadowMapMetas>,
     )>,
-    light_view_query: QueryState<(), With<ShadowView2d>>,
 }
 
 impl FromWorld for Shadow2dMainPassNode {
     fn from_world(world: &mut World) -> Self {
         Self {
             main_view_query: world.query_filtered(),
-            light_view_query: world.query_filtered(),
         }
     }
 }
@@ -380,7 +516,6 @@ impl Node for Shadow2dMainPassNode {
     #[inline]
     fn update(&mut self, world: &mut World) {
         self.main_view_query.update_archetypes(world);
-        self.light_view_query.update_archetypes(world);
     }
 
     fn run<'w>(
@@ -390,20 +525,21 @@ impl Node for Shadow2dMainPassNode {
         world: &'w World,
     ) -> Result<(), NodeRunError> {
         let main_view_entity = graph.view_entity();
-        let Ok((view_target, main_view_offset, gpu_lights, alpha_map)) =
+        let Ok((view_target, main_view_offset, gpu_lights, alpha_map, surfaces, shadow_map_metas)) =
             self.main_view_query.get_manual(world, main_view_entity)
         else {
             return Ok(());
         };
 
-        if self.light_view_query.iter_manual(world).next().is_none() {
-            return Ok(());
-        }
-
         let pipeline = world.resource::<Shadow2dMainPassPipeline>();
+        let pipeline_id = if view_target.is_hdr() {
+            pipeline.hdr_cached_id
+        } else {
+            pipeline.cached_id
+        };
         let Some(render_pipeline) = world
             .resource::<PipelineCache>()
-            .get_render_pipeline(pipeline.cached_id)
+            .get_render_pipeline(pipeline_id)
         else {
             return Ok(());
         };
@@ -414,6 +550,7 @@ impl Node for Shadow2dMainPassNode {
         let view_uniforms = world.resource::<ViewUniforms>();
         let gpu_ambient_light_buffer = world.resource::<GpuAmbientLight2dBuffer>();
         let poisson_disk_buffer = world.resource::<PoissonDiskBuffer>();
+        let light_cookie_storage = world.resource::<LightCookieStorage>();
 
         let bind_group = render_context.render_device().create_bind_group(
             "light_2d_main_pass",
@@ -425,10 +562,20 @@ impl Node for Shadow2dMainPassNode {
                 &pipeline.main_texture_sampler,
                 shadow_map_storage.final_texture_view(),
                 view_uniforms.uniforms.binding().unwrap(),
-                gpu_meta_buffers.shadow_map_meta_buffer_binding(),
+                shadow_map_metas.binding(),
                 gpu_ambient_light_buffer.binding(),
                 poisson_disk_buffer.binding(),
                 gpu_lights.point_lights_binding(),
+                gpu_lights.directional_lights_binding(),
+                gpu_meta_buffers.directional_shadow_meta_binding(),
+                light_cookie_storage.texture_view(),
+                light_cookie_storage.sampler(),
+                gpu_lights.falloff_curves_binding(),
+                shadow_map_storage.moment_map_view(),
+                &surfaces.normal.texture.default_view,
+                &surfaces.material.texture.default_view,
+                &surfaces.emissive.texture.default_view,
+                shadow_map_storage.final_transmittance_map_view(),
             )),
         );
 
@@ -7,15 +7,18 @@ use bevy::{
     },
     ecs::{
         component::Component,
-        entity::Entity,
+        entity::{Entity, EntityHashMap},
         query::With,
         schedule::IntoSystemConfigs,
-        system::{Commands, Query, Res, ResMut, Resource},
+        system::{Commands, Query, Res, ResMut, Resource, SystemParam},
         world::{FromWorld, World},
     },
-    math::{UVec3, UVec4, Vec2, Vec4Swizzles},
+    math::{UVec2, UVec3, UVec4, Vec2, Vec4Swizzles},
     render::{
-        camera::{camera_system, OrthographicProjection, PerspectiveProjection, Projection},
+        camera::{
+            camera_system, ExtractedCamera, OrthographicProjection, PerspectiveProjection,
+            Projection,
+        },
         color::Color,
         extract_resource::ExtractResourcePlugin,
         render_graph::RenderGraphApp,
@@ -23,7 +26,7 @@ use bevy::{
         render_resource::{
             BindingResource, DynamicUniformBuffer, Extent3d, GpuArrayBuffer, SamplerDescriptor,
             Shader, ShaderType, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-            TextureView, TextureViewDescriptor, TextureViewDimension,
+            TextureView, TextureViewDescriptor, TextureViewDimension, UniformBuffer,
         },
         renderer::{RenderDevice, RenderQueue},
         texture::{BevyDefault, ColorAttachment, GpuImage, TextureCache},
@@ -31,28 +34,31 @@ use bevy::{
         Extract, ExtractSchedule, Render, RenderApp, RenderSet,
     },
     transform::{components::GlobalTransform, TransformSystem},
+    utils::warn_once,
 };
 use fast_poisson::Poisson2D;
 
 use crate::{
     ecs::{
-        catalinzz::{MainShadowCameraDriver, ShadowMap2dConfig},
-        PointLight2d, ShadowView2d, SpotLight2d,
+        catalinzz::{
+            LightShadowSettings2d, MainShadowCameraDriver, ShadowFilter2d, ShadowMap2dConfig,
+        },
+        Lighting2dApproach, PointLight2d, ShadowView2d, SpotLight2d,
     },
     render::catalinzz::graph::{
-        Shadow2dDistortPassNode, Shadow2dMainPassNode, Shadow2dMeshPassNode, Shadow2dNode,
-        Shadow2dPrepassNode, Shadow2dReductionNode,
+        Shadow2dBlurPassNode, Shadow2dDistortPassNode, Shadow2dMainPassNode, Shadow2dMeshPassNode,
+        Shadow2dNode, Shadow2dPrepassNode, Shadow2dReductionNode,
     },
 };
 
 use self::pipeline::{
-    Shadow2dDistortPassPipeline, Shadow2dMainPassPipeline, Shadow2dPrepassPipeline,
-    Shadow2dReductionPipeline,
+    Shadow2dBlurPassPipeline, Shadow2dDistortPassPipeline, Shadow2dMainPassPipeline,
+    Shadow2dPrepassPipeline, Shadow2dReductionPipeline,
 };
 
 use bevy::render::view::visibility as bevy_visibility;
 
-use super::{DynamicUniformIndex, ExtractedPointLight2d};
+use super::{caster::TransmittanceView2d, DynamicUniformIndex, ExtractedPointLight2d};
 
 pub mod graph;
 pub mod pipeline;
@@ -63,6 +69,7 @@ pub const SHADOW_DISTORT_PASS_SHADER: Handle<Shader> = Handle::weak_from_u128(13
 pub const SHADOW_PREPASS_SHADER: Handle<Shader> = Handle::weak_from_u128(5321368413218521485631341);
 pub const SHADOW_REDUCTION_PASS_SHADER: Handle<Shader> = Handle::weak_from_u128(485648964891315351);
 pub const SHADOW_MAIN_PASS_SHADER: Handle<Shader> = Handle::weak_from_u128(13643651896413518964153);
+pub const SHADOW_BLUR_PASS_SHADER: Handle<Shader> = Handle::weak_from_u128(8946513548643216854136);
 pub const SHADOW_WORKGROUP_SIZE: UVec3 = UVec3 { x: 16, y: 16, z: 1 };
 
 #[cfg(feature = "compatibility")]
@@ -70,6 +77,21 @@ pub const SHADOW_MAP_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
 #[cfg(not(feature = "compatibility"))]
 pub const SHADOW_MAP_FORMAT: TextureFormat = TextureFormat::Rg32Float;
 
+/// Moments of the reduced shadow map, 4 texels per row:
+/// left, right, up and down in the order of `get_caster_distance_h/v`.
+pub const MOMENT_MAP_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+pub const MOMENT_MAP_WIDTH: u32 = 4;
+
+/// Transmittance of translucent casters, laid out like the shadow map.
+/// Encoded transmittance in rg and distance of the closest translucent caster in ba,
+/// horizontal and vertical respectively.
+pub const TRANSMITTANCE_MAP_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+
+pub const SHADOW_FILTER_PCF: u32 = 0;
+pub const SHADOW_FILTER_VARIANCE: u32 = 1;
+pub const SHADOW_FILTER_EXPONENTIAL: u32 = 2;
+pub const SHADOW_FILTER_MOMENT: u32 = 3;
+
 pub struct CatalinzzApproachPlugin;
 
 impl Plugin for CatalinzzApproachPlugin {
@@ -97,6 +119,13 @@ impl Plugin for CatalinzzApproachPlugin {
             Shader::from_wgsl
         );
 
+        load_internal_asset!(
+            app,
+            SHADOW_BLUR_PASS_SHADER,
+            "shaders/blur_pass.wgsl",
+            Shader::from_wgsl
+        );
+
         load_internal_asset!(
             app,
             SHADOW_MAIN_PASS_SHADER,
@@ -107,6 +136,8 @@ impl Plugin for CatalinzzApproachPlugin {
         app.add_plugins(ExtractResourcePlugin::<ShadowMap2dConfig>::default())
             .init_resource::<ShadowMap2dConfig>()
             .register_type::<ShadowMap2dConfig>()
+            .register_type::<LightShadowSettings2d>()
+            .register_type::<ShadowFilter2d>()
             .add_systems(
                 PostUpdate,
                 (
@@ -134,11 +165,13 @@ impl Plugin for CatalinzzApproachPlugin {
         let render_app = app.sub_app_mut(RenderApp);
 
         render_app
-            .init_resource::<GpuMetaBuffers>()
             .add_systems(ExtractSchedule, (extract_lights, extract_light_view))
             .add_systems(
                 Render,
-                (prepare_lights, prepare_poisson_disk, prepare_main_views)
+                (
+                    (prepare_lights, prepare_poisson_disk, prepare_main_views).run_if(msaa_off),
+                    warn_msaa,
+                )
                     .in_set(RenderSet::Prepare),
             )
             .add_render_graph_node::<Shadow2dMeshPassNode>(Core2d, Shadow2dNode::Shadow2dMeshPass)
@@ -151,6 +184,7 @@ impl Plugin for CatalinzzApproachPlugin {
                 Core2d,
                 Shadow2dNode::Shadow2dReductionPass,
             )
+            .add_render_graph_node::<Shadow2dBlurPassNode>(Core2d, Shadow2dNode::Shadow2dBlurPass)
             .add_render_graph_node::<Shadow2dMainPassNode>(Core2d, Shadow2dNode::Shadow2dMainPass)
             .add_render_graph_edges(
                 Core2d,
@@ -160,6 +194,7 @@ impl Plugin for CatalinzzApproachPlugin {
                     Shadow2dNode::Shadow2dPrepass,
                     Shadow2dNode::Shadow2dDistortPass,
                     Shadow2dNode::Shadow2dReductionPass,
+                    Shadow2dNode::Shadow2dBlurPass,
                     Shadow2dNode::Shadow2dMainPass,
                     Node2d::Bloom,
                 ),
@@ -173,8 +208,10 @@ impl Plugin for CatalinzzApproachPlugin {
             .init_resource::<Shadow2dPrepassPipeline>()
             .init_resource::<Shadow2dDistortPassPipeline>()
             .init_resource::<Shadow2dReductionPipeline>()
+            .init_resource::<Shadow2dBlurPassPipeline>()
             .init_resource::<Shadow2dMainPassPipeline>()
             .init_resource::<ShadowMap2dStorage>()
+            .init_resource::<GpuMetaBuffers>()
             .init_resource::<PoissonDiskBuffer>();
     }
 }
@@ -215,27 +252,38 @@ pub fn extract_lights(
     );
 }
 
+type ExtractShadowLight<L> = (
+    Entity,
+    &'static L,
+    &'static GlobalTransform,
+    Option<&'static LightShadowSettings2d>,
+);
+
 pub fn extract_light_view(
     mut commands: Commands,
-    point_lights_query: Extract<Query<(Entity, &PointLight2d, &GlobalTransform)>>,
-    spot_lights_query: Extract<Query<(Entity, &SpotLight2d, &GlobalTransform)>>,
+    point_lights_query: Extract<Query<ExtractShadowLight<PointLight2d>>>,
+    spot_lights_query: Extract<Query<ExtractShadowLight<SpotLight2d>>>,
     shadow_map_config: Extract<Res<ShadowMap2dConfig>>,
 ) {
     commands.insert_or_spawn_batch(
         point_lights_query
             .iter()
-            .map(|(entity, light, transform)| {
+            .map(|(entity, light, transform, settings)| {
                 let transform = GlobalTransform::from_translation(transform.translation());
                 (
                     entity,
-                    ExtractedView {
-                        projection: shadow_map_config.get_proj_mat(light.range * 2.),
-                        transform,
-                        view_projection: None,
-                        hdr: false,
-                        viewport: UVec4::ZERO,
-                        color_grading: ColorGrading::default(),
-                    },
+                    (
+                        ExtractedView {
+                            projection: shadow_map_config.get_proj_mat(light.max_range() * 2.),
+                            transform,
+                            view_projection: None,
+                            // Only the alpha of casters is rendered into shadow maps.
+                            hdr: false,
+                            viewport: UVec4::ZERO,
+                            color_grading: ColorGrading::default(),
+                        },
+                        settings.copied().unwrap_or_default(),
+                    ),
                 )
             })
             .collect::<Vec<_>>(),
@@ -244,25 +292,29 @@ pub fn extract_light_view(
     commands.insert_or_spawn_batch(
         spot_lights_query
             .iter()
-            .map(|(entity, light, transform)| {
+            .map(|(entity, light, transform, settings)| {
                 let transform = GlobalTransform::from_translation(transform.translation());
                 (
                     entity,
-                    ExtractedView {
-                        projection: shadow_map_config.get_proj_mat(light.range * 2.),
-                        transform,
-                        view_projection: None,
-                        hdr: false,
-                        viewport: UVec4::ZERO,
-                        color_grading: ColorGrading::default(),
-                    },
+                    (
+                        ExtractedView {
+                            projection: shadow_map_config.get_proj_mat(light.range * 2.),
+                            transform,
+                            view_projection: None,
+                            // Only the alpha of casters is rendered into shadow maps.
+                            hdr: false,
+                            viewport: UVec4::ZERO,
+                            color_grading: ColorGrading::default(),
+                        },
+                        settings.copied().unwrap_or_default(),
+                    ),
                 )
             })
             .collect::<Vec<_>>(),
     );
 }
 
-#[derive(ShaderType)]
+#[derive(ShaderType, Clone, Default)]
 pub struct GpuShadowMapMeta {
     pub index: u32,
     pub size: u32,
@@ -271,11 +323,82 @@ pub struct GpuShadowMapMeta {
     pub alpha_threshold: f32,
     pub pcf_samples: u32,
     pub pcf_radius: f32,
+    /// 0 if PCSS is disabled.
+    pub pcss_blocker_samples: u32,
+    pub pcss_max_radius: f32,
+    pub filter_mode: u32,
+    pub blur_radius: u32,
+    pub min_variance: f32,
+    pub light_bleeding_reduction: f32,
+    pub exponent: f32,
+    pub moment_bias: f32,
 }
 
-#[derive(Resource, Default)]
+impl GpuShadowMapMeta {
+    /// Only fills the filter related fields.
+    pub fn from_filter(filter: ShadowFilter2d) -> Self {
+        match filter {
+            ShadowFilter2d::Pcf => Self::default(),
+            ShadowFilter2d::Variance {
+                blur_radius,
+                min_variance,
+                light_bleeding_reduction,
+            } => Self {
+                filter_mode: SHADOW_FILTER_VARIANCE,
+                blur_radius,
+                min_variance,
+                light_bleeding_reduction,
+                ..Default::default()
+            },
+            ShadowFilter2d::Exponential {
+                blur_radius,
+                exponent,
+            } => Self {
+                filter_mode: SHADOW_FILTER_EXPONENTIAL,
+                blur_radius,
+                exponent,
+                ..Default::default()
+            },
+            ShadowFilter2d::Moment {
+                blur_radius,
+                moment_bias,
+            } => Self {
+                filter_mode: SHADOW_FILTER_MOMENT,
+                blur_radius,
+                moment_bias,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(ShaderType, Default)]
+pub struct GpuDirectionalShadowMeta {
+    pub alpha_threshold: f32,
+    pub steps: u32,
+}
+
+/// Selects the [`GpuShadowMapMeta`] of the light rendered by a light view.
+#[derive(Component, ShaderType, Clone, Copy)]
+pub struct GpuShadowMapIndex {
+    pub index: u32,
+}
+
+#[derive(Resource)]
 pub struct GpuMetaBuffers {
-    shadow_map: DynamicUniformBuffer<GpuShadowMapMeta>,
+    light_index: DynamicUniformBuffer<GpuShadowMapIndex>,
+    shadow_map_array: GpuArrayBuffer<GpuShadowMapMeta>,
+    directional_shadow: UniformBuffer<GpuDirectionalShadowMeta>,
+}
+
+impl FromWorld for GpuMetaBuffers {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            light_index: Default::default(),
+            shadow_map_array: GpuArrayBuffer::new(world.resource::<RenderDevice>()),
+            directional_shadow: Default::default(),
+        }
+    }
 }
 
 impl GpuMetaBuffers {
@@ -283,23 +406,45 @@ impl GpuMetaBuffers {
     pub fn push_light_meta(
         &mut self,
         meta: GpuShadowMapMeta,
-    ) -> DynamicUniformIndex<GpuShadowMapMeta> {
-        DynamicUniformIndex::new(self.shadow_map.push(&meta))
+    ) -> DynamicUniformIndex<GpuShadowMapIndex> {
+        let index = GpuShadowMapIndex { index: meta.index };
+        self.shadow_map_array.push(meta);
+        DynamicUniformIndex::new(self.light_index.push(&index))
+    }
+
+    #[inline]
+    pub fn set_directional_shadow_meta(&mut self, meta: GpuDirectionalShadowMeta) {
+        self.directional_shadow.set(meta);
     }
 
     #[inline]
     pub fn clear(&mut self) {
-        self.shadow_map.clear();
+        self.light_index.clear();
+        self.shadow_map_array.clear();
     }
 
     #[inline]
     pub fn write_buffers(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
-        self.shadow_map.write_buffer(render_device, render_queue);
+        self.light_index.write_buffer(render_device, render_queue);
+        self.shadow_map_array
+            .write_buffer(render_device, render_queue);
+        self.directional_shadow
+            .write_buffer(render_device, render_queue);
+    }
+
+    #[inline]
+    pub fn light_index_binding(&self) -> BindingResource<'_> {
+        self.light_index.binding().unwrap()
+    }
+
+    #[inline]
+    pub fn shadow_map_metas_binding(&self) -> BindingResource<'_> {
+        self.shadow_map_array.binding().unwrap()
     }
 
     #[inline]
-    pub fn shadow_map_meta_buffer_binding(&self) -> BindingResource {
-        self.shadow_map.binding().unwrap()
+    pub fn directional_shadow_meta_binding(&self) -> BindingResource<'_> {
+        self.directional_shadow.binding().unwrap()
     }
 }
 
@@ -314,7 +459,9 @@ pub struct ShadowMap2dStorage {
     meta: ShadowMap2dMeta,
     primary_shadow_map: Option<GpuImage>,
     secondary_shadow_map: Option<GpuImage>,
-    work_group_count_per_light: UVec3,
+    moment_map: Option<GpuImage>,
+    primary_transmittance_map: Option<GpuImage>,
+    secondary_transmittance_map: Option<GpuImage>,
     work_group_count_total: UVec3,
     num_reductions: u32,
 }
@@ -326,16 +473,19 @@ impl ShadowMap2dStorage {
         }
 
         self.meta = meta;
-        self.primary_shadow_map = Some(self.create_shadow_map(render_device, SHADOW_MAP_FORMAT));
-        self.secondary_shadow_map = Some(self.create_shadow_map(render_device, SHADOW_MAP_FORMAT));
-        self.work_group_count_per_light = UVec3 {
+        self.primary_shadow_map =
+            Some(self.create_shadow_map(render_device, SHADOW_MAP_FORMAT, meta.size));
+        self.secondary_shadow_map =
+            Some(self.create_shadow_map(render_device, SHADOW_MAP_FORMAT, meta.size));
+        self.moment_map =
+            Some(self.create_shadow_map(render_device, MOMENT_MAP_FORMAT, MOMENT_MAP_WIDTH));
+        self.primary_transmittance_map =
+            Some(self.create_shadow_map(render_device, TRANSMITTANCE_MAP_FORMAT, meta.size));
+        self.secondary_transmittance_map =
+            Some(self.create_shadow_map(render_device, TRANSMITTANCE_MAP_FORMAT, meta.size));
+        self.work_group_count_total = UVec3 {
             x: meta.size.div_ceil(SHADOW_WORKGROUP_SIZE.x),
             y: meta.size.div_ceil(SHADOW_WORKGROUP_SIZE.y),
-            z: 1,
-        };
-        self.work_group_count_total = UVec3 {
-            x: self.work_group_count_per_light.x,
-            y: self.work_group_count_per_light.y,
             z: meta.count,
         };
         self.num_reductions = meta.size.trailing_zeros();
@@ -359,7 +509,7 @@ impl ShadowMap2dStorage {
 
     #[inline]
     pub fn final_texture_view(&self) -> &TextureView {
-        if self.num_reductions % 2 == 0 {
+        if self.num_reductions.is_multiple_of(2) {
             self.texture_view_secondary()
         } else {
             self.texture_view_primary()
@@ -367,8 +517,35 @@ impl ShadowMap2dStorage {
     }
 
     #[inline]
-    pub fn work_group_count_per_light(&self) -> UVec3 {
-        self.work_group_count_per_light
+    pub fn moment_map_view(&self) -> &TextureView {
+        &self.moment_map.as_ref().unwrap().texture_view
+    }
+
+    #[inline]
+    pub fn transmittance_map_view_primary(&self) -> &TextureView {
+        &self
+            .primary_transmittance_map
+            .as_ref()
+            .unwrap()
+            .texture_view
+    }
+
+    #[inline]
+    pub fn transmittance_map_view_secondary(&self) -> &TextureView {
+        &self
+            .secondary_transmittance_map
+            .as_ref()
+            .unwrap()
+            .texture_view
+    }
+
+    #[inline]
+    pub fn final_transmittance_map_view(&self) -> &TextureView {
+        if self.num_reductions.is_multiple_of(2) {
+            self.transmittance_map_view_secondary()
+        } else {
+            self.transmittance_map_view_primary()
+        }
     }
 
     #[inline]
@@ -381,13 +558,18 @@ impl ShadowMap2dStorage {
         self.num_reductions
     }
 
-    fn create_shadow_map(&self, render_device: &RenderDevice, format: TextureFormat) -> GpuImage {
+    fn create_shadow_map(
+        &self,
+        render_device: &RenderDevice,
+        format: TextureFormat,
+        width: u32,
+    ) -> GpuImage {
         let meta = self.meta;
 
         let shadow_map = render_device.create_texture(&TextureDescriptor {
             label: Some("shadow_map_2d"),
             size: Extent3d {
-                width: meta.size,
+                width,
                 height: meta.size,
                 depth_or_array_layers: meta.count,
             },
@@ -415,7 +597,7 @@ impl ShadowMap2dStorage {
                 label: Some("shadow_map_2d_sampler"),
                 ..Default::default()
             }),
-            size: Vec2::splat(meta.size as f32),
+            size: Vec2::new(width as f32, meta.size as f32),
             mip_level_count: 0,
         }
     }
@@ -481,49 +663,112 @@ pub fn prepare_poisson_disk(
     buffer.regen_by_shadow_map(&shadow_map_config, &render_device, &render_queue);
 }
 
+fn msaa_off(msaa: Res<Msaa>) -> bool {
+    *msaa == Msaa::Off
+}
+
+/// Sprites are rendered into single sampled shadow maps, so cameras using `catalinzz`
+/// are left unlit while MSAA is on.
+fn warn_msaa(main_views: Query<&Lighting2dApproach, With<ViewTarget>>, msaa: Res<Msaa>) {
+    if *msaa != Msaa::Off
+        && main_views
+            .iter()
+            .any(|approach| *approach == Lighting2dApproach::Catalinzz)
+    {
+        warn_once!("MSAA is not supported by catalinzz, cameras using it are left unlit.");
+    }
+}
+
+#[derive(SystemParam)]
+pub struct ShadowMapResources<'w> {
+    shadow_map_config: Res<'w, ShadowMap2dConfig>,
+    shadow_map_storage: ResMut<'w, ShadowMap2dStorage>,
+    gpu_meta_buffers: ResMut<'w, GpuMetaBuffers>,
+}
+
 pub fn prepare_lights(
     mut commands: Commands,
     mut texture_cache: ResMut<TextureCache>,
-    main_views: Query<Entity, With<ViewTarget>>,
-    point_lights: Query<(Entity, &ExtractedPointLight2d)>,
-    shadow_map_config: Res<ShadowMap2dConfig>,
-    mut shadow_map_storage: ResMut<ShadowMap2dStorage>,
-    mut gpu_meta_buffers: ResMut<GpuMetaBuffers>,
+    main_views: Query<
+        (
+            Entity,
+            &ExtractedCamera,
+            &VisibleEntities,
+            &Lighting2dApproach,
+        ),
+        With<ViewTarget>,
+    >,
+    point_lights: Query<(
+        Entity,
+        &ExtractedPointLight2d,
+        Option<&LightShadowSettings2d>,
+    )>,
+    resources: ShadowMapResources,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
-    msaa: Res<Msaa>,
 ) {
-    assert_eq!(*msaa, Msaa::Off, "MSAA is not supported yet!");
-
-    let point_light_count = point_lights.iter().count();
-    if point_light_count == 0 {
-        return;
-    }
-
+    let ShadowMapResources {
+        shadow_map_config,
+        mut shadow_map_storage,
+        mut gpu_meta_buffers,
+    } = resources;
     gpu_meta_buffers.clear();
+    gpu_meta_buffers.set_directional_shadow_meta(GpuDirectionalShadowMeta {
+        alpha_threshold: shadow_map_config.alpha_threshold,
+        steps: shadow_map_config.directional_shadow_steps,
+    });
 
+    let point_light_count = point_lights.iter().count();
     let mut point_lights = point_lights.iter().collect::<Vec<_>>();
-    radsort::sort_by_key(&mut point_lights, |(_, light)| light.id);
+    radsort::sort_by_key(&mut point_lights, |(_, light, _)| light.id);
+    let mut light_metas = EntityHashMap::default();
+
+    // Keep a 1x1 placeholder shadow map when there's no point light,
+    // as the main pass still needs to run for directional lights.
+    let mut max_shadow_map_size = 1;
+
+    for (light_index, (light_entity, light, settings)) in point_lights.into_iter().enumerate() {
+        let mut light_config = settings
+            .map(|s| s.apply(&shadow_map_config))
+            .unwrap_or(*shadow_map_config);
+        if !light_config.size.is_power_of_two() {
+            warn_once!(
+                "Shadow map size {} is not a power of 2, rounding up to {}.",
+                light_config.size,
+                light_config.size.next_power_of_two()
+            );
+            light_config.size = light_config.size.next_power_of_two();
+        }
+        max_shadow_map_size = max_shadow_map_size.max(light_config.size);
 
-    for (light_index, (light_entity, _)) in point_lights.into_iter().enumerate() {
-        // TODO support different settings for different lights
-        let meta_index = gpu_meta_buffers.push_light_meta(GpuShadowMapMeta {
+        let meta = GpuShadowMapMeta {
             index: light_index as u32,
-            size: shadow_map_config.size,
-            offset: shadow_map_config.offset,
-            bias: shadow_map_config.bias,
-            alpha_threshold: shadow_map_config.alpha_threshold,
-            pcf_samples: shadow_map_config.pcf.samples,
-            pcf_radius: shadow_map_config.pcf.radius,
-        });
+            size: light_config.size,
+            offset: light_config.offset,
+            bias: light_config.bias,
+            alpha_threshold: light_config.alpha_threshold,
+            pcf_samples: light_config.pcf.samples,
+            pcf_radius: light_config.pcf.radius,
+            pcss_blocker_samples: light_config
+                .pcss
+                .map(|pcss| pcss.blocker_samples)
+                .unwrap_or_default(),
+            pcss_max_radius: light_config
+                .pcss
+                .map(|pcss| pcss.max_radius)
+                .unwrap_or_default(),
+            ..GpuShadowMapMeta::from_filter(light_config.filter)
+        };
+        let meta_index = gpu_meta_buffers.push_light_meta(meta.clone());
+        light_metas.insert(light_entity, (light.id, meta));
 
         let point_light_view_mesh_texture = texture_cache.get(
             &render_device,
             TextureDescriptor {
                 label: Some("point_light_view_mesh_texture"),
                 size: Extent3d {
-                    width: shadow_map_config.size,
-                    height: shadow_map_config.size,
+                    width: light_config.size,
+                    height: light_config.size,
                     depth_or_array_layers: 1,
                 },
                 mip_level_count: 1,
@@ -542,36 +787,86 @@ pub fn prepare_lights(
                 Some(Color::NONE),
             ),
         };
+        let transmittance_view = TransmittanceView2d::new(
+            UVec2::splat(light_config.size),
+            &mut texture_cache,
+            &render_device,
+        );
 
         commands
             .entity(light_entity)
-            .insert((meta_index, shadow_view));
+            .insert((meta_index, shadow_view, transmittance_view));
     }
 
     gpu_meta_buffers.write_buffers(&render_device, &render_queue);
 
     shadow_map_storage.try_update(
         ShadowMap2dMeta {
-            count: point_light_count as u32,
-            size: shadow_map_config.size,
+            count: point_light_count.max(1) as u32,
+            size: max_shadow_map_size,
         },
         &render_device,
     );
 
-    if let Some(shadow_camera) = main_views.iter().next() {
+    let main_views = main_views
+        .iter()
+        .filter(|(.., approach)| **approach == Lighting2dApproach::Catalinzz)
+        .collect::<Vec<_>>();
+
+    // Every camera indexes the shadow maps of the lights it sees, in the same order
+    // as its `GpuLights2d`.
+    for (main_view_entity, _, visible_entities, _) in main_views.iter().copied() {
+        let mut visible_metas = visible_entities
+            .iter()
+            .filter_map(|entity| light_metas.get(entity))
+            .collect::<Vec<_>>();
+        radsort::sort_by_key(&mut visible_metas, |(id, _)| *id);
+
+        let mut metas = GpuArrayBuffer::new(&render_device);
+        for (_, meta) in visible_metas {
+            metas.push(meta.clone());
+        }
+        metas.write_buffer(&render_device, &render_queue);
+
+        commands
+            .entity(main_view_entity)
+            .insert(ViewShadowMapMetas(metas));
+    }
+
+    // The shadow maps are shared by all cameras, and rendered by the first one.
+    if let Some((shadow_camera, ..)) = main_views
+        .into_iter()
+        .min_by_key(|(_, camera, ..)| camera.order)
+    {
         commands
             .entity(shadow_camera)
             .insert(MainShadowCameraDriver);
     }
 }
 
+/// The [`GpuShadowMapMeta`]s of the point lights visible to a camera, whose `index`
+/// points to the shared shadow map of each light.
+#[derive(Component)]
+pub struct ViewShadowMapMetas(GpuArrayBuffer<GpuShadowMapMeta>);
+
+impl ViewShadowMapMetas {
+    #[inline]
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.0.binding().unwrap()
+    }
+}
+
 fn prepare_main_views(
     mut commands: Commands,
     mut texture_cache: ResMut<TextureCache>,
-    main_views: Query<(Entity, &ExtractedView), With<ViewTarget>>,
+    main_views: Query<(Entity, &ExtractedView, &Lighting2dApproach), With<ViewTarget>>,
     render_device: Res<RenderDevice>,
 ) {
-    for (main_view_entity, main_view) in main_views.iter() {
+    for (main_view_entity, main_view, approach) in main_views.iter() {
+        if *approach != Lighting2dApproach::Catalinzz {
+            continue;
+        }
+
         let viewport = main_view.viewport.zw();
         let attachment = texture_cache.get(
             &render_device,
@@ -585,6 +880,7 @@ fn prepare_main_views(
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
+                // Only the alpha of casters is rendered, through the `CasterView2d`.
                 format: TextureFormat::bevy_default(),
                 usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
                 view_formats: &[],
@@ -15,17 +15,21 @@ use bevy::{
         },
         renderer::RenderDevice,
         texture::BevyDefault,
-        view::ViewUniform,
+        view::{ViewTarget, ViewUniform},
     },
 };
 
 use bevy::render::render_resource::binding_types as binding;
 
-use crate::render::light::{GpuAmbientLight2d, GpuPointLight2d};
+use crate::render::light::{
+    falloff_curves_shader_def, GpuAmbientLight2d, GpuDirectionalLight2d, GpuPointLight2d,
+};
 
 use super::{
-    GpuShadowMapMeta, SHADOW_DISTORT_PASS_SHADER, SHADOW_MAIN_PASS_SHADER, SHADOW_MAP_FORMAT,
-    SHADOW_PREPASS_SHADER, SHADOW_REDUCTION_PASS_SHADER,
+    GpuDirectionalShadowMeta, GpuShadowMapIndex, GpuShadowMapMeta, MOMENT_MAP_FORMAT,
+    SHADOW_BLUR_PASS_SHADER, SHADOW_DISTORT_PASS_SHADER, SHADOW_MAIN_PASS_SHADER,
+    SHADOW_MAP_FORMAT, SHADOW_PREPASS_SHADER, SHADOW_REDUCTION_PASS_SHADER,
+    TRANSMITTANCE_MAP_FORMAT,
 };
 
 fn get_shader_defs() -> Vec<ShaderDefVal> {
@@ -58,8 +62,17 @@ impl FromWorld for Shadow2dPrepassPipeline {
                         SHADOW_MAP_FORMAT,
                         StorageTextureAccess::WriteOnly,
                     ),
-                    // Shadow map meta
-                    binding::uniform_buffer::<GpuShadowMapMeta>(true),
+                    // Light index
+                    binding::uniform_buffer::<GpuShadowMapIndex>(true),
+                    // Transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::WriteOnly,
+                    ),
+                    // Shadow map metas
+                    binding::storage_buffer_read_only::<Vec<GpuShadowMapMeta>>(false),
+                    // Transmittance of translucent casters
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
                 ),
             ),
         );
@@ -107,8 +120,18 @@ impl FromWorld for Shadow2dDistortPassPipeline {
                         SHADOW_MAP_FORMAT,
                         StorageTextureAccess::WriteOnly,
                     ),
-                    // Shadow map meta
-                    binding::uniform_buffer::<GpuShadowMapMeta>(false),
+                    // Shadow map metas
+                    binding::storage_buffer_read_only::<Vec<GpuShadowMapMeta>>(false),
+                    // Source transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::ReadOnly,
+                    ),
+                    // Destination transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::WriteOnly,
+                    ),
                 ),
             ),
         );
@@ -156,10 +179,20 @@ impl FromWorld for Shadow2dReductionPipeline {
                         SHADOW_MAP_FORMAT,
                         StorageTextureAccess::ReadWrite,
                     ),
-                    // Shadow map meta
-                    binding::uniform_buffer::<GpuShadowMapMeta>(false),
+                    // Shadow map metas
+                    binding::storage_buffer_read_only::<Vec<GpuShadowMapMeta>>(false),
                     // Reduction time
                     binding::uniform_buffer::<u32>(true),
+                    // Source transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::ReadWrite,
+                    ),
+                    // Destination transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::ReadWrite,
+                    ),
                 ),
             ),
         );
@@ -182,9 +215,59 @@ impl FromWorld for Shadow2dReductionPipeline {
     }
 }
 
+#[derive(Resource)]
+pub struct Shadow2dBlurPassPipeline {
+    pub cached_id: CachedComputePipelineId,
+    pub blur_pass_layout: BindGroupLayout,
+}
+
+impl FromWorld for Shadow2dBlurPassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let blur_pass_layout = render_device.create_bind_group_layout(
+            "light_2d_blur_pass_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    // Reduced shadow map
+                    binding::texture_storage_2d_array(
+                        SHADOW_MAP_FORMAT,
+                        StorageTextureAccess::ReadOnly,
+                    ),
+                    // Moment map
+                    binding::texture_storage_2d_array(
+                        MOMENT_MAP_FORMAT,
+                        StorageTextureAccess::WriteOnly,
+                    ),
+                    // Shadow map metas
+                    binding::storage_buffer_read_only::<Vec<GpuShadowMapMeta>>(false),
+                ),
+            ),
+        );
+
+        let cached_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("light_2d_blur_pipeline".into()),
+                layout: vec![blur_pass_layout.clone()],
+                push_constant_ranges: vec![],
+                shader: SHADOW_BLUR_PASS_SHADER,
+                shader_defs: get_shader_defs(),
+                entry_point: "main".into(),
+            });
+
+        Self {
+            cached_id,
+            blur_pass_layout,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct Shadow2dMainPassPipeline {
     pub cached_id: CachedRenderPipelineId,
+    pub hdr_cached_id: CachedRenderPipelineId,
     pub main_pass_layout: BindGroupLayout,
     pub main_texture_sampler: Sampler,
 }
@@ -211,13 +294,38 @@ impl FromWorld for Shadow2dMainPassPipeline {
                     ),
                     // Shadow views
                     binding::uniform_buffer::<ViewUniform>(true),
-                    // Shadow map meta
-                    binding::uniform_buffer::<GpuShadowMapMeta>(false),
+                    // Shadow map metas
+                    binding::storage_buffer_read_only::<Vec<GpuShadowMapMeta>>(false),
                     // Ambient light
                     binding::uniform_buffer::<GpuAmbientLight2d>(false),
                     binding::storage_buffer_read_only::<Vec<Vec2>>(false),
                     // Point lights
                     binding::storage_buffer_read_only::<Vec<GpuPointLight2d>>(false),
+                    // Directional lights
+                    binding::storage_buffer_read_only::<Vec<GpuDirectionalLight2d>>(false),
+                    // Directional shadow meta
+                    binding::uniform_buffer::<GpuDirectionalShadowMeta>(false),
+                    // Light cookies
+                    binding::texture_2d_array(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                    // Falloff curves, see `falloff_curves_shader_def`
+                    binding::storage_buffer_read_only::<Vec<f32>>(false),
+                    // Moment map
+                    binding::texture_storage_2d_array(
+                        MOMENT_MAP_FORMAT,
+                        StorageTextureAccess::ReadOnly,
+                    ),
+                    // Surface normals
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface material
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface emissive
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Transmittance map
+                    binding::texture_storage_2d_array(
+                        TRANSMITTANCE_MAP_FORMAT,
+                        StorageTextureAccess::ReadOnly,
+                    ),
                 ),
             ),
         );
@@ -237,32 +345,39 @@ impl FromWorld for Shadow2dMainPassPipeline {
             border_color: None,
         });
 
-        let cached_id =
-            world
-                .resource_mut::<PipelineCache>()
-                .queue_render_pipeline(RenderPipelineDescriptor {
-                    label: Some("light_2d_main_pass_pipeline".into()),
-                    layout: vec![main_pass_layout.clone()],
-                    vertex: fullscreen_shader_vertex_state(),
-                    fragment: Some(FragmentState {
-                        shader: SHADOW_MAIN_PASS_SHADER,
-                        shader_defs: get_shader_defs(),
-                        entry_point: "fragment".into(),
-                        // entry_point: "dbg_output_shadow_map".into(),
-                        targets: vec![Some(ColorTargetState {
-                            format: TextureFormat::bevy_default(),
-                            blend: None,
-                            write_mask: ColorWrites::ALL,
-                        })],
-                    }),
-                    primitive: PrimitiveState::default(),
-                    depth_stencil: None,
-                    multisample: MultisampleState::default(),
-                    push_constant_ranges: vec![],
-                });
+        // Writes into the view target, which is `Rgba16Float` for hdr cameras.
+        let descriptor = |label: &'static str, format: TextureFormat| RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: vec![main_pass_layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: SHADOW_MAIN_PASS_SHADER,
+                shader_defs: [get_shader_defs(), vec![falloff_curves_shader_def(14)]].concat(),
+                entry_point: "fragment".into(),
+                // entry_point: "dbg_output_shadow_map".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
 
         Self {
-            cached_id,
+            cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_main_pass_pipeline",
+                TextureFormat::bevy_default(),
+            )),
+            hdr_cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_main_pass_hdr_pipeline",
+                ViewTarget::TEXTURE_FORMAT_HDR,
+            )),
             main_pass_layout,
             main_texture_sampler,
         }
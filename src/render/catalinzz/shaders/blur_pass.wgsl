@@ -0,0 +1,97 @@
+#import bevy_incandescent::catalinzz::types::{
+    ShadowMapMeta, SHADOW_FILTER_EXPONENTIAL, SHADOW_FILTER_MOMENT, SHADOW_FILTER_PCF,
+}
+
+@group(0) @binding(0)
+var shadow_map: texture_storage_2d_array<
+#ifdef COMPATIBILITY
+    rgba32float,
+#else
+    rg32float,
+#endif
+    read
+>;
+
+@group(0) @binding(1)
+var moment_map: texture_storage_2d_array<rgba32float, write>;
+
+@group(0) @binding(2)
+var<storage> shadow_map_metas: array<ShadowMapMeta>;
+
+// Caster distance normalized into [0, 1].
+// The 4 columns of the moment map are left, right, up and down,
+// which are columns 1 and 2 of the red and green channels of the reduced shadow map.
+fn get_caster_depth(column: u32, row: u32, light_index: u32) -> f32 {
+    let sample = textureLoad(shadow_map, vec2u(column % 2u + 1u, row), light_index);
+    return select(sample.r, sample.g, column >= 2u);
+}
+
+fn get_moments(depth: f32, shadow_map_meta: ShadowMapMeta) -> vec4f {
+    switch shadow_map_meta.filter_mode {
+        case SHADOW_FILTER_EXPONENTIAL: {
+            return vec4f(exp(shadow_map_meta.exponent * depth), 0., 0., 0.);
+        }
+        case SHADOW_FILTER_MOMENT: {
+            let sqr = depth * depth;
+            return vec4f(depth, sqr, sqr * depth, sqr * sqr);
+        }
+        default: {
+            return vec4f(depth, depth * depth, 0., 0.);
+        }
+    }
+}
+
+// Rows continue into the neighboring directions at the diagonals,
+// where both directions see the same ray.
+fn wrap_row(column: u32, row: i32, size: i32) -> vec2u {
+    if row >= 0 && row < size {
+        return vec2u(column, u32(row));
+    }
+
+    var wrapped = vec2i(0);
+    let before = row < 0;
+    switch column {
+        case 0u: {
+            wrapped = select(vec2i(3, row - size), vec2i(2, -1 - row), before);
+        }
+        case 1u: {
+            wrapped = select(vec2i(3, 2 * size - 1 - row), vec2i(2, size + row), before);
+        }
+        case 2u: {
+            wrapped = select(vec2i(1, row - size), vec2i(0, -1 - row), before);
+        }
+        default: {
+            wrapped = select(vec2i(1, 2 * size - 1 - row), vec2i(0, size + row), before);
+        }
+    }
+    return vec2u(u32(wrapped.x), u32(clamp(wrapped.y, 0, size - 1)));
+}
+
+// The reduced shadow map stores a single caster distance per ray, and the moments
+// already account for the penumbra along the rays. So the only axis to blur is
+// across the rays, which goes all around the light through the 4 directions.
+@compute @workgroup_size(16, 16, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
+    let px = invocation_id.xy;
+    let light_index = invocation_id.z;
+    let shadow_map_meta = shadow_map_metas[light_index];
+    let size = shadow_map_meta.size;
+
+    if px.x >= 4u || px.y >= size || shadow_map_meta.filter_mode == SHADOW_FILTER_PCF {
+        return;
+    }
+
+    let radius = i32(shadow_map_meta.blur_radius);
+    let sigma = max(f32(radius) / 2., 0.0001);
+    var moments = vec4f(0.);
+    var total_weight = 0.;
+
+    for (var offset = -radius; offset <= radius; offset++) {
+        let texel = wrap_row(px.x, i32(px.y) + offset, i32(size));
+        let weight = exp(-f32(offset * offset) / (2. * sigma * sigma));
+        moments += get_moments(get_caster_depth(texel.x, texel.y, light_index), shadow_map_meta) * weight;
+        total_weight += weight;
+    }
+
+    textureStore(moment_map, px, light_index, moments / total_weight);
+}
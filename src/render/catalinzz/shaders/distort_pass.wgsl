@@ -1,4 +1,6 @@
-#import bevy_incandescent::catalinzz::types::ShadowMapMeta;
+#import bevy_incandescent::catalinzz::types::{
+    ShadowMapMeta, decode_transmittance, encode_transmittance,
+};
 
 @group(0) @binding(0)
 var source_shadow_map: texture_storage_2d_array<
@@ -21,22 +23,43 @@ var dest_shadow_map: texture_storage_2d_array<
 >;
 
 @group(0) @binding(2)
-var<uniform> shadow_map_meta: ShadowMapMeta;
+var<storage> shadow_map_metas: array<ShadowMapMeta>;
+
+@group(0) @binding(3)
+var source_transmittance_map: texture_storage_2d_array<rgba32float, read>;
+
+@group(0) @binding(4)
+var dest_transmittance_map: texture_storage_2d_array<rgba32float, write>;
+
+fn get_distorted_px(px: vec2u, size: u32) -> vec2i {
+    let uv = vec2f(px) / vec2f(f32(size));
+    let uv_ndc = uv * 2. - 1.;
+    let v0 = (uv_ndc.y * abs(uv_ndc.x) + 1.) / 2.;
+    let distorted_ndc = vec2f(uv.x, v0);
+    return vec2i(distorted_ndc * vec2f(f32(size)));
+}
+
+// Keeps the transmittance only at the first texel of each translucent caster along the ray,
+// so multiplying the texels of the ray counts every caster once.
+fn get_entering_transmittance(current: vec4f, previous: vec4f) -> vec2f {
+    let entering = vec2<bool>(
+        all(decode_transmittance(previous.r) == vec3f(1.)),
+        all(decode_transmittance(previous.g) == vec3f(1.)),
+    );
+    return select(vec2f(encode_transmittance(vec3f(1.))), current.rg, entering);
+}
 
 @compute @workgroup_size(16, 16, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     let px = invocation_id.xy;
     let light_index = invocation_id.z;
+    let size = shadow_map_metas[light_index].size;
 
-    if px.x >= shadow_map_meta.size || px.y >= shadow_map_meta.size {
+    if px.x >= size || px.y >= size {
         return;
     }
 
-    let uv = vec2f(px) / vec2f(f32(shadow_map_meta.size));
-    let uv_ndc = uv * 2. - 1.;
-    let v0 = (uv_ndc.y * abs(uv_ndc.x) + 1.) / 2.;
-    let distorted_ndc = vec2f(uv.x, v0);
-    let distorted_px = vec2i(distorted_ndc * vec2f(f32(shadow_map_meta.size)));
+    let distorted_px = get_distorted_px(px, size);
     
     let color = vec4f(
         textureLoad(source_shadow_map, distorted_px, light_index).r,
@@ -51,4 +74,29 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
         light_index,
         color,
     );
+
+    // Encoded transmittance in rg and distance of the closest translucent caster in ba.
+    let transmittance_h = textureLoad(source_transmittance_map, distorted_px, light_index);
+    let transmittance_v = textureLoad(source_transmittance_map, distorted_px.yx, light_index);
+    let transmittance = vec4f(transmittance_h.r, transmittance_v.r, transmittance_h.b, transmittance_v.b);
+
+    // Rays start from the center of the rows, the first texel of each half has nothing before it.
+    let half_size = size / 2u;
+    var previous = vec4f(encode_transmittance(vec3f(1.)));
+    if px.x != half_size && px.x + 1u != half_size {
+        let previous_px = get_distorted_px(vec2u(select(px.x - 1u, px.x + 1u, px.x < half_size), px.y), size);
+        previous = vec4f(
+            textureLoad(source_transmittance_map, previous_px, light_index).r,
+            textureLoad(source_transmittance_map, previous_px.yx, light_index).r,
+            0.,
+            0.,
+        );
+    }
+
+    textureStore(
+        dest_transmittance_map,
+        px,
+        light_index,
+        vec4f(get_entering_transmittance(transmittance, previous), transmittance.ba),
+    );
 }
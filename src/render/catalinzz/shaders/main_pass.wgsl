@@ -1,10 +1,16 @@
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
 #import bevy_render::view::View
 #import bevy_incandescent::{
-    catalinzz::types::ShadowMapMeta,
-    lighting::get_distance_attenuation,
-    math::{is_point_inside_sector},
-    types::{AmbientLight2d, PointLight2d},
+    catalinzz::types::{
+        DirectionalShadowMeta, ShadowMapMeta, SHADOW_FILTER_EXPONENTIAL, SHADOW_FILTER_MOMENT,
+        SHADOW_FILTER_PCF, decode_transmittance,
+    },
+    lighting::{
+        get_cookie_uv, get_distance_attenuation, get_elliptical_rel, get_normal_shading,
+        get_point_light_falloff, get_specular_shading, screen_to_world, NO_LIGHT_COOKIE,
+    },
+    math::{get_sector_falloff, is_point_inside_sector},
+    types::{AmbientLight2d, DirectionalLight2d, PointLight2d},
 }
 
 @group(0) @binding(0)
@@ -33,7 +39,7 @@ var shadow_map: texture_storage_2d_array<
 var<uniform> main_view: View;
 
 @group(0) @binding(6)
-var<uniform> shadow_map_meta: ShadowMapMeta;
+var<storage> shadow_map_metas: array<ShadowMapMeta>;
 
 @group(0) @binding(7)
 var<uniform> ambient_light: AmbientLight2d;
@@ -44,16 +50,43 @@ var<storage> poisson_disk: array<vec2f>;
 @group(0) @binding(9)
 var<storage> point_lights: array<PointLight2d>;
 
+@group(0) @binding(10)
+var<storage> directional_lights: array<DirectionalLight2d>;
+
+@group(0) @binding(11)
+var<uniform> directional_shadow_meta: DirectionalShadowMeta;
+
+@group(0) @binding(12)
+var light_cookies: texture_2d_array<f32>;
+
+@group(0) @binding(13)
+var light_cookie_sampler: sampler;
+
+@group(0) @binding(15)
+var moment_map: texture_storage_2d_array<rgba32float, read>;
+
+@group(0) @binding(16)
+var surface_normal: texture_2d<f32>;
+
+@group(0) @binding(17)
+var surface_material: texture_2d<f32>;
+
+@group(0) @binding(18)
+var surface_emissive: texture_2d<f32>;
+
+@group(0) @binding(19)
+var transmittance_map: texture_storage_2d_array<rgba32float, read>;
+
 fn get_caster_distance_h(rel_ss: vec2f, i_light: u32) -> f32 {
     let v0 = (rel_ss.y / abs(rel_ss.x) + 1.) / 2.;
-    let px = vec2f(2., f32(shadow_map_meta.size)) * vec2f(rel_ss.x / 2. + 1., v0);
-    return textureLoad(shadow_map, vec2i(px), i_light).r * 2.;
+    let px = vec2f(2., f32(shadow_map_metas[i_light].size)) * vec2f(rel_ss.x / 2. + 1., v0);
+    return textureLoad(shadow_map, vec2i(px), shadow_map_metas[i_light].index).r * 2.;
 }
 
 fn get_caster_distance_v(rel_ss: vec2f, i_light: u32) -> f32 {
     let v0 = (rel_ss.x / abs(rel_ss.y) + 1.) / 2.;
-    let px = vec2f(2., f32(shadow_map_meta.size)) * vec2f(rel_ss.y / 2. + 1., v0);
-    return textureLoad(shadow_map, vec2i(px), i_light).g * 2.;
+    let px = vec2f(2., f32(shadow_map_metas[i_light].size)) * vec2f(rel_ss.y / 2. + 1., v0);
+    return textureLoad(shadow_map, vec2i(px), shadow_map_metas[i_light].index).g * 2.;
 }
 
 fn get_caster_distance(rel_ss: vec2f, i_light: u32) -> f32 {
@@ -64,33 +97,215 @@ fn get_caster_distance(rel_ss: vec2f, i_light: u32) -> f32 {
     }
 }
 
+// Tints the light by the translucent casters in between, looked up like `get_caster_distance`.
+fn get_transmittance(rel_ss: vec2f, i_light: u32) -> vec3f {
+    let size = f32(shadow_map_metas[i_light].size);
+    var transmittance = vec2f(0.);
+    if abs(rel_ss.y) < abs(rel_ss.x) {
+        let v0 = (rel_ss.y / abs(rel_ss.x) + 1.) / 2.;
+        let px = vec2f(2., size) * vec2f(rel_ss.x / 2. + 1., v0);
+        transmittance = textureLoad(transmittance_map, vec2i(px), shadow_map_metas[i_light].index).rb;
+    } else {
+        let v0 = (rel_ss.x / abs(rel_ss.y) + 1.) / 2.;
+        let px = vec2f(2., size) * vec2f(rel_ss.y / 2. + 1., v0);
+        transmittance = textureLoad(transmittance_map, vec2i(px), shadow_map_metas[i_light].index).ga;
+    }
+
+    if transmittance.y * 2. > length(rel_ss) - shadow_map_metas[i_light].bias {
+        return vec3f(1.);
+    }
+    return decode_transmittance(transmittance.x);
+}
+
 fn pcf(rel_ss: vec2f, sample_radius: f32, i_light: u32) -> f32 {
+    let shadow_map_meta = &shadow_map_metas[i_light];
+    let samples = min((*shadow_map_meta).pcf_samples, arrayLength(&poisson_disk));
+
     var visibility = 0.;
-    for (var i: u32 = 0; i < shadow_map_meta.pcf_samples; i++) {
+    for (var i: u32 = 0; i < samples; i++) {
         let sample_ss = rel_ss + poisson_disk[i] * sample_radius;
         let dist = get_caster_distance(sample_ss, i_light);
         
-        if dist > length(sample_ss) - shadow_map_meta.bias {
+        if dist > length(sample_ss) - (*shadow_map_meta).bias {
             visibility += 1.;
         }
     }
-    visibility /= f32(shadow_map_meta.pcf_samples);
+    visibility /= f32(samples);
     return visibility;
 }
 
+// https://developer.download.nvidia.com/shaderlibrary/docs/shadow_PCSS.pdf
+// The light is treated as a disk with the given radius, everything is relative to the range.
+fn pcss(rel_ss: vec2f, light_radius_rel: f32, min_radius_rel: f32, max_radius_rel: f32, i_light: u32) -> f32 {
+    let shadow_map_meta = &shadow_map_metas[i_light];
+    let samples = min((*shadow_map_meta).pcss_blocker_samples, arrayLength(&poisson_disk));
+    let receiver_dist = length(rel_ss);
+    let search_radius = max(light_radius_rel, min_radius_rel);
+
+    var blocker_dist = 0.;
+    var blockers = 0u;
+    for (var i: u32 = 0; i < samples; i++) {
+        let sample_ss = rel_ss + poisson_disk[i] * search_radius;
+        let dist = get_caster_distance(sample_ss, i_light);
+
+        if dist <= length(sample_ss) - (*shadow_map_meta).bias {
+            blocker_dist += dist;
+            blockers += 1u;
+        }
+    }
+
+    if blockers == 0u {
+        return 1.;
+    }
+
+    blocker_dist /= f32(blockers);
+    let penumbra = (receiver_dist - blocker_dist) * light_radius_rel / max(blocker_dist, 0.0001);
+    return pcf(rel_ss, clamp(penumbra, min_radius_rel, max_radius_rel), i_light);
+}
+
+fn get_cookie_color(light: PointLight2d, rel_ss: vec2f, range_ss: f32) -> vec3f {
+    if light.cookie_index == NO_LIGHT_COOKIE {
+        return vec3f(1.);
+    }
+
+    let uv = get_cookie_uv(rel_ss, range_ss, light.rotation);
+    let cookie = textureSampleLevel(light_cookies, light_cookie_sampler, uv, light.cookie_index, 0.);
+    return cookie.rgb * cookie.a;
+}
+
+// Fetches the blurred moments in the same direction as `get_caster_distance`.
+fn get_moments(rel_ss: vec2f, i_light: u32) -> vec4f {
+    let size = shadow_map_metas[i_light].size;
+    let layer = shadow_map_metas[i_light].index;
+    var column = 0u;
+    var v0 = 0.;
+    if abs(rel_ss.y) < abs(rel_ss.x) {
+        column = select(1u, 0u, rel_ss.x < 0.);
+        v0 = (rel_ss.y / abs(rel_ss.x) + 1.) / 2.;
+    } else {
+        column = select(3u, 2u, rel_ss.y < 0.);
+        v0 = (rel_ss.x / abs(rel_ss.y) + 1.) / 2.;
+    }
+
+    // Storage textures can't be sampled, so interpolate between rows manually.
+    let row = clamp(v0 * f32(size) - 0.5, 0., f32(size - 1u));
+    let row0 = u32(row);
+    let row1 = min(row0 + 1u, size - 1u);
+    return mix(
+        textureLoad(moment_map, vec2u(column, row0), layer),
+        textureLoad(moment_map, vec2u(column, row1), layer),
+        fract(row),
+    );
+}
+
+fn variance_visibility(moments: vec4f, depth: f32, min_variance: f32, light_bleeding_reduction: f32) -> f32 {
+    if depth <= moments.x {
+        return 1.;
+    }
+
+    let variance = max(moments.y - moments.x * moments.x, min_variance);
+    let d = depth - moments.x;
+    let p_max = variance / (variance + d * d);
+    return saturate((p_max - light_bleeding_reduction) / (1. - light_bleeding_reduction));
+}
+
+// https://momentsingraphics.de/Media/I3D2015/MomentShadowMapping.pdf
+// Algorithm 2, Hamburger 4MSM.
+fn moment_visibility(moments: vec4f, depth: f32, moment_bias: f32) -> f32 {
+    let b = mix(moments, vec4f(0.5), moment_bias);
+
+    // Cholesky decomposition of the Hankel matrix.
+    let l32_d22 = -b.x * b.y + b.z;
+    let d22 = -b.x * b.x + b.y;
+    let squared_depth_variance = -b.y * b.y + b.w;
+    let d33_d22 = dot(vec2f(squared_depth_variance, -l32_d22), vec2f(d22, l32_d22));
+    let inv_d22 = 1. / d22;
+    let l32 = l32_d22 * inv_d22;
+
+    var c = vec3f(1., depth, depth * depth);
+    c.y -= b.x;
+    c.z -= b.y + l32 * c.y;
+    c.y *= inv_d22;
+    c.z *= d22 / d33_d22;
+    c.y -= l32 * c.z;
+    c.x -= dot(c.yz, b.xy);
+
+    // Roots of the quadratic polynomial.
+    let p = c.y / c.z;
+    let q = c.x / c.z;
+    let r = sqrt(max(p * p * 0.25 - q, 0.));
+    let z1 = -p * 0.5 - r;
+    let z2 = -p * 0.5 + r;
+
+    var switch_val = vec4f(0.);
+    if z2 < depth {
+        switch_val = vec4f(z1, depth, 1., 1.);
+    } else if z1 < depth {
+        switch_val = vec4f(depth, z1, 0., 1.);
+    }
+
+    let quotient = (switch_val.x * z2 - b.x * (switch_val.x + z2) + b.y)
+                   / ((z2 - switch_val.y) * (depth - z1));
+    return 1. - saturate(switch_val.z + switch_val.w * quotient);
+}
+
+fn filtered_visibility(rel_ss: vec2f, i_light: u32) -> f32 {
+    let shadow_map_meta = &shadow_map_metas[i_light];
+    // The same unit as the reduced shadow map.
+    let depth = (length(rel_ss) - (*shadow_map_meta).bias) / 2.;
+    let moments = get_moments(rel_ss, i_light);
+
+    switch (*shadow_map_meta).filter_mode {
+        case SHADOW_FILTER_EXPONENTIAL: {
+            return saturate(moments.x * exp(-(*shadow_map_meta).exponent * depth));
+        }
+        case SHADOW_FILTER_MOMENT: {
+            return moment_visibility(moments, depth, (*shadow_map_meta).moment_bias);
+        }
+        default: {
+            return variance_visibility(
+                moments,
+                depth,
+                (*shadow_map_meta).min_variance,
+                (*shadow_map_meta).light_bleeding_reduction,
+            );
+        }
+    }
+}
+
 fn get_alpha(uv: vec2f, i_light: u32) -> f32 {
     return textureSample(alpha_map, alpha_map_sampler, uv).a;
 }
 
+// Marches through the alpha map of casters towards the light to find casters in between.
+fn get_directional_visibility(px: vec2f, screen_size: vec2f, dir: vec2f, shadow_length: f32) -> f32 {
+    let steps = max(directional_shadow_meta.steps, 1u);
+    let step = shadow_length / f32(steps);
+
+    for (var i = 1u; i <= steps; i++) {
+        let uv = (px + dir * step * f32(i)) / screen_size;
+        if any(uv < vec2f(0.)) || any(uv > vec2f(1.)) {
+            break;
+        }
+
+        if textureSampleLevel(alpha_map, alpha_map_sampler, uv, 0.).a > directional_shadow_meta.alpha_threshold {
+            return 0.;
+        }
+    }
+    return 1.;
+}
+
 @fragment
 fn dbg_output_shadow_map(in: FullscreenVertexOutput) -> @location(0) vec4f {
-    return textureLoad(shadow_map, vec2u(in.uv * vec2f(shadow_map_meta.size)), 0);
+    return textureLoad(shadow_map, vec2u(in.uv * vec2f(shadow_map_metas[0].size)), 0);
 }
 
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
     let screen_size = 2. * vec2f(main_view.inverse_projection[0][0], main_view.inverse_projection[1][1]);
     let px = in.uv * screen_size;
+    let surface = textureSample(surface_normal, main_tex_sampler, in.uv);
+    let material = textureSample(surface_material, main_tex_sampler, in.uv);
 
     var color = vec3f(0.);
     for (var i_light = 0u; i_light < arrayLength(&point_lights); i_light++) {
@@ -99,28 +314,68 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
         let light_range_ss = max((*light).range_ss, 0.) * screen_size.x;
         let light_radius_ss = max((*light).radius_ss, 0.) * screen_size.x;
         let light_color = (*light).color;
+        let shadow_map_meta = &shadow_map_metas[i_light];
 
-        let rel_px_ss = px - light_pos_ss + shadow_map_meta.offset;
-        let rel_px_dist = length(rel_px_ss);
+        let rel_px_ws = screen_to_world(px - light_pos_ss, main_view.view);
+        // Shadow maps are aligned with the world, with y pointing downwards like the screen.
+        let rel_px_ss = rel_px_ws * vec2f(1., -1.) + (*shadow_map_meta).offset;
         let rel_ss = rel_px_ss / light_range_ss;
         let rel_dist = length(rel_ss);
-        let pcf_radius_rel = shadow_map_meta.pcf_radius / light_range_ss;
+        let pcf_radius_rel = (*shadow_map_meta).pcf_radius / light_range_ss;
+        // Shadow maps cover the whole range, so only the lighting is elliptical.
+        let rel_range_ws = get_elliptical_rel(
+            rel_px_ss * vec2f(1., -1.), (*light).range_scale, (*light).range_rotation
+        );
 
-        if is_point_inside_sector(rel_px_ss * vec2f(1., -1.), vec2f(0.), light_range_ss, (*light).angles) {
-            if get_alpha(in.uv, i_light) > shadow_map_meta.alpha_threshold {
+        if is_point_inside_sector(rel_range_ws, vec2f(0.), light_range_ss, (*light).angles) {
+            if get_alpha(in.uv, i_light) > (*shadow_map_meta).alpha_threshold {
                 continue;
             }
 
-            var visibility = pcf(rel_ss, pcf_radius_rel, i_light);
-            visibility *= 1. - saturate(
-                (rel_px_dist - light_radius_ss) / (light_range_ss - light_radius_ss)
+            var visibility = 0.;
+            if (*shadow_map_meta).filter_mode != SHADOW_FILTER_PCF {
+                visibility = filtered_visibility(rel_ss, i_light);
+            } else if (*shadow_map_meta).pcss_blocker_samples == 0u {
+                visibility = pcf(rel_ss, pcf_radius_rel, i_light);
+            } else {
+                visibility = pcss(
+                    rel_ss,
+                    light_radius_ss / light_range_ss,
+                    pcf_radius_rel,
+                    max((*shadow_map_meta).pcss_max_radius, (*shadow_map_meta).pcf_radius) / light_range_ss,
+                    i_light,
+                );
+            }
+            let to_light_ws = -rel_px_ws;
+            let light_height_ss = max((*light).height_ss, 0.) * screen_size.x;
+            // Only the shadows are squared, so falloffs and shading match the other approaches.
+            let falloff = get_point_light_falloff(*light, length(rel_range_ws), light_radius_ss, light_range_ss)
+                          * get_sector_falloff(rel_range_ws, (*light).angles, (*light).inner_extent);
+            let shading = get_normal_shading(surface, to_light_ws, light_height_ss)
+                          + get_specular_shading(surface, material, to_light_ws, light_height_ss);
+            let cookie_color = get_cookie_color(
+                *light,
+                get_elliptical_rel(rel_px_ws, (*light).range_scale, (*light).range_rotation) * vec2f(1., -1.),
+                light_range_ss,
             );
-            let attend_color = visibility * visibility * (*light).intensity * light_color.rgb;
+            let transmittance = get_transmittance(rel_ss, i_light);
+            let attend_color = visibility * visibility * falloff * shading * (*light).intensity
+                               * light_color.rgb * cookie_color * transmittance;
             color += attend_color;
         }
     }
 
-    return textureSample(main_tex, main_tex_sampler, in.uv)
-           * vec4f(ambient_light.color.rgb * ambient_light.intensity, 1.)
-           + vec4f(color, 0.);
+    for (var i_light = 0u; i_light < arrayLength(&directional_lights); i_light++) {
+        let light = &directional_lights[i_light];
+        let shadow_length_ss = max((*light).shadow_length_ss, 0.) * screen_size.x;
+
+        let visibility = get_directional_visibility(px, screen_size, -(*light).direction_ss, shadow_length_ss);
+        color += visibility * (*light).intensity * (*light).color.rgb;
+    }
+
+    let receiver = max(material.b, 0.);
+    let ambient = mix(vec3f(1.), ambient_light.color.rgb * ambient_light.intensity, saturate(receiver));
+    return textureSample(main_tex, main_tex_sampler, in.uv) * vec4f(ambient, 1.)
+           + vec4f(color * receiver, 0.)
+           + vec4f(textureSample(surface_emissive, main_tex_sampler, in.uv).rgb, 0.);
 }
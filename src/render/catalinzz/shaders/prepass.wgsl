@@ -1,4 +1,4 @@
-#import bevy_incandescent::catalinzz::types::ShadowMapMeta;
+#import bevy_incandescent::catalinzz::types::{ShadowMapMeta, encode_transmittance};
 
 @group(0) @binding(0)
 var main_tex: texture_2d<f32>;
@@ -14,19 +14,39 @@ var shadow_map: texture_storage_2d_array<
 >;
 
 @group(0) @binding(2)
-var<uniform> shadow_map_meta: ShadowMapMeta;
+var<uniform> light_index: u32;
+
+@group(0) @binding(3)
+var transmittance_map: texture_storage_2d_array<rgba32float, write>;
+
+@group(0) @binding(4)
+var<storage> shadow_map_metas: array<ShadowMapMeta>;
+
+@group(0) @binding(5)
+var translucent_tex: texture_2d<f32>;
 
 @compute @workgroup_size(16, 16, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     let px = invocation_id.xy;
+    let shadow_map_meta = shadow_map_metas[light_index];
 
     if px.x >= shadow_map_meta.size || px.y >= shadow_map_meta.size {
         return;
     }
 
+    let caster = textureLoad(main_tex, px, 0);
+    let dist = length(vec2f(px) / vec2f(f32(shadow_map_meta.size)) - vec2f(0.5)) * 2.;
     var d = 1.;
-    if textureLoad(main_tex, px, 0).a > shadow_map_meta.alpha_threshold {
-        d = length(vec2f(px) / vec2f(f32(shadow_map_meta.size)) - vec2f(0.5)) * 2.;
+    var transmittance = vec3f(1.);
+    var translucent_d = 1.;
+    if caster.a > shadow_map_meta.alpha_threshold {
+        d = dist;
+    } else {
+        // Only translucent casters are drawn into this texture, which is cleared to white.
+        transmittance = textureLoad(translucent_tex, px, 0).rgb;
+        if any(transmittance < vec3f(1.)) {
+            translucent_d = dist;
+        }
     }
 
     textureStore(
@@ -35,4 +55,11 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
         shadow_map_meta.index,
         vec4f(d, 0., 0., 0.),
     );
+
+    textureStore(
+        transmittance_map,
+        px,
+        shadow_map_meta.index,
+        vec4f(encode_transmittance(transmittance), 0., translucent_d, 0.),
+    );
 }
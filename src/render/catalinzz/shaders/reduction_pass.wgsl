@@ -1,4 +1,6 @@
-#import bevy_incandescent::catalinzz::types::ShadowMapMeta;
+#import bevy_incandescent::catalinzz::types::{
+    ShadowMapMeta, decode_transmittance, encode_transmittance,
+};
 
 @group(0) @binding(0)
 var source_shadow_map: texture_storage_2d_array<
@@ -21,18 +23,60 @@ var dest_shadow_map: texture_storage_2d_array<
 >;
 
 @group(0) @binding(2)
-var<uniform> shadow_map_meta: ShadowMapMeta;
+var<storage> shadow_map_metas: array<ShadowMapMeta>;
 
 @group(0) @binding(3)
 var<uniform> reduction_time: u32;
 
+@group(0) @binding(4)
+var source_transmittance_map: texture_storage_2d_array<rgba32float, read_write>;
+
+@group(0) @binding(5)
+var dest_transmittance_map: texture_storage_2d_array<rgba32float, read_write>;
+
+// Multiplies the translucent casters along the ray, which only keep their transmittance
+// at the texel entering them. It can't be told from the reduced maps in which order
+// casters were hit, so everything behind the closest one is tinted by all of them.
+fn reduce_transmittance(a: vec4f, b: vec4f) -> vec4f {
+    return vec4f(
+        encode_transmittance(decode_transmittance(a.r) * decode_transmittance(b.r)),
+        encode_transmittance(decode_transmittance(a.g) * decode_transmittance(b.g)),
+        min(a.ba, b.ba),
+    );
+}
+
 @compute @workgroup_size(16, 16, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     let px = vec2u(invocation_id.x * 2, invocation_id.y);
-    let size = shadow_map_meta.size;
     let light_index = invocation_id.z;
+    let size = shadow_map_metas[light_index].size;
+
+    if px.x >= size || px.y >= size {
+        return;
+    }
 
-    if px.x >= size >> reduction_time || px.y >= size {
+    // Lights with smaller shadow maps finish reducing earlier,
+    // so just carry their results over to the destination.
+    if size >> reduction_time <= 1u {
+        textureStore(dest_shadow_map, px, light_index, textureLoad(source_shadow_map, px, light_index));
+        let next_px = vec2u(px.x + 1, px.y);
+        textureStore(dest_shadow_map, next_px, light_index, textureLoad(source_shadow_map, next_px, light_index));
+        textureStore(
+            dest_transmittance_map,
+            px,
+            light_index,
+            textureLoad(source_transmittance_map, px, light_index),
+        );
+        textureStore(
+            dest_transmittance_map,
+            next_px,
+            light_index,
+            textureLoad(source_transmittance_map, next_px, light_index),
+        );
+        return;
+    }
+
+    if px.x >= size >> reduction_time {
         return;
     }
 
@@ -47,4 +91,14 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
         light_index,
         color,
     );
+
+    textureStore(
+        dest_transmittance_map,
+        vec2u(px.x >> 1, px.y),
+        light_index,
+        reduce_transmittance(
+            textureLoad(source_transmittance_map, px, light_index),
+            textureLoad(source_transmittance_map, vec2u(px.x + 1, px.y), light_index),
+        ),
+    );
 }
@@ -1,5 +1,10 @@
 #define_import_path bevy_incandescent::catalinzz::types
 
+const SHADOW_FILTER_PCF: u32 = 0u;
+const SHADOW_FILTER_VARIANCE: u32 = 1u;
+const SHADOW_FILTER_EXPONENTIAL: u32 = 2u;
+const SHADOW_FILTER_MOMENT: u32 = 3u;
+
 struct ShadowMapMeta {
     index: u32,
     size: u32,
@@ -8,4 +13,30 @@ struct ShadowMapMeta {
     alpha_threshold: f32,
     pcf_samples: u32,
     pcf_radius: f32,
+    // 0 if PCSS is disabled.
+    pcss_blocker_samples: u32,
+    pcss_max_radius: f32,
+    filter_mode: u32,
+    blur_radius: u32,
+    min_variance: f32,
+    light_bleeding_reduction: f32,
+    exponent: f32,
+    moment_bias: f32,
+}
+
+struct DirectionalShadowMeta {
+    alpha_threshold: f32,
+    steps: u32,
+}
+
+// Transmittance is stored in a single channel with 8 bits per color,
+// as a float that is exactly representable.
+fn encode_transmittance(transmittance: vec3f) -> f32 {
+    let q = vec3u(round(saturate(transmittance) * 255.));
+    return f32(q.r | (q.g << 8u) | (q.b << 16u));
+}
+
+fn decode_transmittance(encoded: f32) -> vec3f {
+    let q = u32(encoded);
+    return vec3f(vec3u(q, q >> 8u, q >> 16u) & vec3u(255u)) / 255.;
 }
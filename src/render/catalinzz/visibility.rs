@@ -16,7 +16,10 @@ use bevy::{
 };
 use thread_local::ThreadLocal;
 
-use crate::ecs::{catalinzz::ShadowMap2dConfig, PointLight2d, ShadowCaster2d, SpotLight2d};
+use crate::ecs::{
+    catalinzz::ShadowMap2dConfig, OccluderTexture2d, PointLight2d, ShadowCaster2d, SpotLight2d,
+    TranslucentShadowCaster2d,
+};
 
 pub fn update_point_light_frusta(
     mut lights_query: Query<
@@ -28,8 +31,8 @@ pub fn update_point_light_frusta(
     lights_query
         .par_iter_mut()
         .for_each(|(transform, mut frustum, light)| {
-            let view_proj =
-                shadow_map_config.get_proj_mat(light.range) * transform.compute_matrix().inverse();
+            let view_proj = shadow_map_config.get_proj_mat(light.max_range())
+                * transform.compute_matrix().inverse();
             *frustum = Frustum::from_view_projection_custom_far(
                 &view_proj,
                 &transform.translation(),
@@ -76,6 +79,8 @@ pub fn check_caster_visibility(
             Option<&Aabb>,
             &GlobalTransform,
             Has<NoFrustumCulling>,
+            Has<OccluderTexture2d>,
+            Has<TranslucentShadowCaster2d>,
         ),
         With<ShadowCaster2d>,
     >,
@@ -93,6 +98,8 @@ pub fn check_caster_visibility(
                 maybe_model_aabb,
                 transform,
                 no_frustum_culling,
+                has_occluder_texture,
+                is_translucent,
             ) = query_item;
 
             // Skip computing visibility for entities that are configured to be hidden.
@@ -126,6 +133,11 @@ pub fn check_caster_visibility(
             }
 
             view_visibility.set();
+            // Rendered by `render_casters()` instead, but still needs to be extracted.
+            if has_occluder_texture || is_translucent {
+                return;
+            }
+
             let cell = thread_queues.get_or_default();
             let mut queue = cell.take();
             queue.push(entity);
@@ -0,0 +1,264 @@
+use bevy::{
+    asset::{AssetEvent, AssetId},
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::{
+        event::EventReader,
+        system::{Query, Res, ResMut, Resource},
+        world::{FromWorld, World},
+    },
+    math::Vec2,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{
+            AddressMode, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
+            Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
+            PipelineCache, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
+            StoreOp, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+            TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{GpuImage, Image},
+        Extract,
+    },
+};
+
+use bevy::render::render_resource::binding_types as binding;
+
+use super::{ExtractedPointLight2d, LIGHT_COOKIE_BLIT_SHADER};
+
+pub const LIGHT_COOKIE_SIZE: u32 = 256;
+pub const LIGHT_COOKIE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+pub const NO_LIGHT_COOKIE: u32 = u32::MAX;
+
+#[derive(Resource)]
+pub struct LightCookieBlitPipeline {
+    pub cached_id: CachedRenderPipelineId,
+    pub blit_layout: BindGroupLayout,
+    pub sampler: Sampler,
+}
+
+impl FromWorld for LightCookieBlitPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let blit_layout = render_device.create_bind_group_layout(
+            "light_cookie_blit_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // Source cookie
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("light_cookie_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let cached_id =
+            world
+                .resource::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("light_cookie_blit_pipeline".into()),
+                    layout: vec![blit_layout.clone()],
+                    push_constant_ranges: vec![],
+                    vertex: fullscreen_shader_vertex_state(),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    fragment: Some(FragmentState {
+                        shader: LIGHT_COOKIE_BLIT_SHADER,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![Some(ColorTargetState {
+                            format: LIGHT_COOKIE_FORMAT,
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                });
+
+        Self {
+            cached_id,
+            blit_layout,
+            sampler,
+        }
+    }
+}
+
+/// Cookies of all lights, resampled into layers of a single texture array
+/// so that they can be bound at once.
+#[derive(Resource)]
+pub struct LightCookieStorage {
+    cookies: Vec<AssetId<Image>>,
+    /// Set when one of the `cookies` is modified, so that it gets resampled.
+    modified: bool,
+    texture: GpuImage,
+}
+
+impl FromWorld for LightCookieStorage {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let sampler = world.resource::<LightCookieBlitPipeline>().sampler.clone();
+
+        Self {
+            cookies: Vec::new(),
+            modified: false,
+            texture: Self::create_texture(1, sampler, render_device),
+        }
+    }
+}
+
+impl LightCookieStorage {
+    #[inline]
+    pub fn get_index(&self, cookie: Option<AssetId<Image>>) -> u32 {
+        cookie
+            .and_then(|id| self.cookies.iter().position(|c| *c == id))
+            .map(|index| index as u32)
+            .unwrap_or(NO_LIGHT_COOKIE)
+    }
+
+    #[inline]
+    pub fn texture_view(&self) -> &TextureView {
+        &self.texture.texture_view
+    }
+
+    #[inline]
+    pub fn sampler(&self) -> &Sampler {
+        &self.texture.sampler
+    }
+
+    fn create_texture(layers: u32, sampler: Sampler, render_device: &RenderDevice) -> GpuImage {
+        let texture = render_device.create_texture(&TextureDescriptor {
+            label: Some("light_cookies"),
+            size: Extent3d {
+                width: LIGHT_COOKIE_SIZE,
+                height: LIGHT_COOKIE_SIZE,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: LIGHT_COOKIE_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        GpuImage {
+            texture_view: texture.create_view(&TextureViewDescriptor {
+                label: Some("light_cookies_view"),
+                format: Some(LIGHT_COOKIE_FORMAT),
+                dimension: Some(TextureViewDimension::D2Array),
+                array_layer_count: Some(layers),
+                ..Default::default()
+            }),
+            texture_format: LIGHT_COOKIE_FORMAT,
+            texture,
+            sampler,
+            size: Vec2::splat(LIGHT_COOKIE_SIZE as f32),
+            mip_level_count: 1,
+        }
+    }
+}
+
+pub fn extract_light_cookie_events(
+    mut cookie_storage: ResMut<LightCookieStorage>,
+    mut image_events: Extract<EventReader<AssetEvent<Image>>>,
+) {
+    for event in image_events.read() {
+        if let AssetEvent::Modified { id } = event {
+            if cookie_storage.cookies.contains(id) {
+                cookie_storage.modified = true;
+            }
+        }
+    }
+}
+
+pub fn prepare_light_cookies(
+    lights_query: Query<&ExtractedPointLight2d>,
+    mut cookie_storage: ResMut<LightCookieStorage>,
+    blit_pipeline: Res<LightCookieBlitPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let mut lights = lights_query.iter().collect::<Vec<_>>();
+    radsort::sort_by_key(&mut lights, |light| light.id);
+
+    let mut cookies = Vec::new();
+    for cookie in lights.into_iter().filter_map(|light| light.cookie) {
+        if images.get(cookie).is_some() && !cookies.contains(&cookie) {
+            cookies.push(cookie);
+        }
+    }
+
+    if cookies == cookie_storage.cookies && !cookie_storage.modified {
+        return;
+    }
+
+    let Some(render_pipeline) = pipeline_cache.get_render_pipeline(blit_pipeline.cached_id) else {
+        return;
+    };
+
+    let texture = LightCookieStorage::create_texture(
+        cookies.len().max(1) as u32,
+        blit_pipeline.sampler.clone(),
+        &render_device,
+    );
+    let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("light_cookie_blit_encoder"),
+    });
+
+    for (layer, cookie) in cookies.iter().enumerate() {
+        let source = images.get(*cookie).unwrap();
+        let bind_group = render_device.create_bind_group(
+            "light_cookie_blit_bind_group",
+            &blit_pipeline.blit_layout,
+            &BindGroupEntries::sequential((&source.texture_view, &blit_pipeline.sampler)),
+        );
+        let layer_view = texture.texture.create_view(&TextureViewDescriptor {
+            label: Some("light_cookie_layer_view"),
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer as u32,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+
+        let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("light_cookie_blit_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &layer_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Default::default()),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    render_queue.submit([command_encoder.finish()]);
+
+    cookie_storage.cookies = cookies;
+    cookie_storage.modified = false;
+    cookie_storage.texture = texture;
+}
@@ -0,0 +1,251 @@
+use bevy::{
+    ecs::{
+        query::QueryState,
+        system::lifetimeless::Read,
+        world::{FromWorld, World},
+    },
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_resource::{
+            BindGroupEntries, LoadOp, Operations, PipelineCache, RenderPassColorAttachment,
+            RenderPassDepthStencilAttachment, RenderPassDescriptor, StoreOp,
+        },
+        renderer::RenderContext,
+        view::{ViewTarget, ViewUniformOffset, ViewUniforms},
+    },
+};
+
+use crate::render::{
+    cookie::LightCookieStorage,
+    light::{GpuAmbientLight2dBuffer, GpuLights2d},
+    surface::ViewSurfaces2d,
+    universal_buffers::NumberBuffer,
+};
+
+use super::{
+    pipeline::{Shadow2dLightPassPipeline, Shadow2dMainPassPipeline},
+    LightBuffer2d, ShadowEdgeBuffer,
+};
+
+#[derive(RenderLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum Shadow2dNode {
+    Shadow2dLightPass,
+    Shadow2dMainPass,
+}
+
+type LightPassView = (
+    Read<ViewUniformOffset>,
+    Read<GpuLights2d>,
+    Read<LightBuffer2d>,
+    Read<ViewSurfaces2d>,
+);
+
+pub struct Shadow2dLightPassNode {
+    main_view_query: QueryState<LightPassView>,
+}
+
+impl FromWorld for Shadow2dLightPassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for Shadow2dLightPassNode {
+    #[inline]
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok((view_offset, gpu_lights, light_buffer, surfaces)) =
+            self.main_view_query.get_manual(world, graph.view_entity())
+        else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<Shadow2dLightPassPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let edge_buffer = world.resource::<ShadowEdgeBuffer>();
+        let number_buffer = world.resource::<NumberBuffer>();
+        let view_uniforms = world.resource::<ViewUniforms>();
+        let light_cookie_storage = world.resource::<LightCookieStorage>();
+
+        let (
+            Some(point_volume),
+            Some(directional_volume),
+            Some(point_light),
+            Some(directional_light),
+        ) = (
+            pipeline_cache.get_render_pipeline(pipeline.point_volume_id),
+            pipeline_cache.get_render_pipeline(pipeline.directional_volume_id),
+            pipeline_cache.get_render_pipeline(pipeline.point_light_id),
+            pipeline_cache.get_render_pipeline(pipeline.directional_light_id),
+        )
+        else {
+            return Ok(());
+        };
+
+        let volume_bind_group = render_context.render_device().create_bind_group(
+            "light_2d_shadow_volume_bind_group",
+            &pipeline.volume_layout,
+            &BindGroupEntries::sequential((
+                view_uniforms.uniforms.binding().unwrap(),
+                gpu_lights.point_lights_binding(),
+                gpu_lights.directional_lights_binding(),
+                number_buffer.binding(),
+            )),
+        );
+
+        let light_bind_group = render_context.render_device().create_bind_group(
+            "light_2d_light_pass_bind_group",
+            &pipeline.light_layout,
+            &BindGroupEntries::sequential((
+                view_uniforms.uniforms.binding().unwrap(),
+                gpu_lights.point_lights_binding(),
+                gpu_lights.directional_lights_binding(),
+                number_buffer.binding(),
+                light_cookie_storage.texture_view(),
+                light_cookie_storage.sampler(),
+                gpu_lights.falloff_curves_binding(),
+                &surfaces.normal.texture.default_view,
+                &surfaces.material.texture.default_view,
+                &pipeline.sampler,
+            )),
+        );
+
+        // Light indices are passed through the number buffer.
+        let lights = (0..gpu_lights.point_lights_len())
+            .map(|index| (index, point_volume, point_light))
+            .chain(
+                (0..gpu_lights.directional_lights_len())
+                    .map(|index| (index, directional_volume, directional_light)),
+            )
+            .collect::<Vec<_>>();
+
+        if lights.is_empty() {
+            // Still clear the light buffer.
+            render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("light_2d_light_pass"),
+                color_attachments: &[Some(light_buffer.light.get_attachment())],
+                ..Default::default()
+            });
+            return Ok(());
+        }
+
+        for (index, volume_pipeline, light_pipeline) in lights {
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("light_2d_light_pass"),
+                color_attachments: &[Some(light_buffer.light.get_attachment())],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &light_buffer.stencil.default_view,
+                    depth_ops: None,
+                    stencil_ops: Some(Operations {
+                        load: LoadOp::Clear(0),
+                        store: StoreOp::Discard,
+                    }),
+                }),
+                ..Default::default()
+            });
+            let offsets = [view_offset.offset, number_buffer.get_index(index)];
+
+            if let Some(edges) = edge_buffer.edges().buffer() {
+                render_pass.set_render_pipeline(volume_pipeline);
+                render_pass.set_bind_group(0, &volume_bind_group, &offsets);
+                render_pass.set_vertex_buffer(0, edges.slice(..));
+                render_pass.draw(0..6, 0..edge_buffer.edges().len() as u32);
+            }
+
+            render_pass.set_render_pipeline(light_pipeline);
+            render_pass.set_bind_group(0, &light_bind_group, &offsets);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Shadow2dMainPassNode {
+    main_view_query: QueryState<(Read<ViewTarget>, Read<LightBuffer2d>, Read<ViewSurfaces2d>)>,
+}
+
+impl FromWorld for Shadow2dMainPassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for Shadow2dMainPassNode {
+    #[inline]
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok((view_target, light_buffer, surfaces)) =
+            self.main_view_query.get_manual(world, graph.view_entity())
+        else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<Shadow2dMainPassPipeline>();
+        let pipeline_id = if view_target.is_hdr() {
+            pipeline.hdr_cached_id
+        } else {
+            pipeline.cached_id
+        };
+        let Some(render_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let gpu_ambient_light_buffer = world.resource::<GpuAmbientLight2dBuffer>();
+        let post_process = view_target.post_process_write();
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "light_2d_geometry_main_pass_bind_group",
+            &pipeline.main_pass_layout,
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &pipeline.main_texture_sampler,
+                &light_buffer.light.texture.default_view,
+                gpu_ambient_light_buffer.binding(),
+                &surfaces.material.texture.default_view,
+                &surfaces.emissive.texture.default_view,
+            )),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("light_2d_main_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+use bevy::{
+    app::{App, Plugin},
+    asset::{load_internal_asset, Assets, Handle},
+    core_pipeline::core_2d::graph::{Core2d, Node2d},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    math::{Vec2, Vec4},
+    render::{
+        color::Color,
+        render_graph::RenderGraphApp,
+        render_resource::{
+            BufferUsages, BufferVec, Extent3d, Shader, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{CachedTexture, ColorAttachment, Image, TextureCache},
+        view::{ExtractedView, InheritedVisibility, ViewTarget},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    sprite::{Sprite, TextureAtlas, TextureAtlasLayout},
+    transform::components::GlobalTransform,
+};
+
+use crate::ecs::{
+    Lighting2dApproach, ShadowCaster2d, ShadowCasterShape2d, TranslucentShadowCaster2d,
+};
+
+use self::{
+    graph::{Shadow2dLightPassNode, Shadow2dMainPassNode, Shadow2dNode},
+    pipeline::{Shadow2dLightPassPipeline, Shadow2dMainPassPipeline},
+};
+
+use super::{light::GpuLights2d, universal_buffers::NumberBuffer};
+
+pub mod graph;
+pub mod pipeline;
+
+pub const SHADOW_VOLUME_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(6543168746513218946513547861320);
+pub const SHADOW_LIGHT_PASS_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(3215468743513548631354687413254);
+pub const SHADOW_MAIN_PASS_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(8746513254687431325468743513546);
+
+/// Accumulated light of all point, spot and directional lights.
+pub const LIGHT_BUFFER_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const SHADOW_STENCIL_FORMAT: TextureFormat = TextureFormat::Stencil8;
+
+pub struct GeometryApproachPlugin;
+
+impl Plugin for GeometryApproachPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            SHADOW_VOLUME_SHADER,
+            "shaders/shadow_volume.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            SHADOW_LIGHT_PASS_SHADER,
+            "shaders/light_pass.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            SHADOW_MAIN_PASS_SHADER,
+            "shaders/main_pass.wgsl",
+            Shader::from_wgsl
+        );
+
+        let render_app = app.sub_app_mut(RenderApp);
+
+        render_app
+            .init_resource::<ShadowEdgeBuffer>()
+            .add_systems(ExtractSchedule, extract_caster_outlines)
+            .add_systems(
+                Render,
+                (
+                    prepare_shadow_edges,
+                    prepare_light_buffers,
+                    prepare_light_indices.after(super::prepare_lights),
+                )
+                    .in_set(RenderSet::Prepare),
+            )
+            .add_render_graph_node::<Shadow2dLightPassNode>(Core2d, Shadow2dNode::Shadow2dLightPass)
+            .add_render_graph_node::<Shadow2dMainPassNode>(Core2d, Shadow2dNode::Shadow2dMainPass)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::MainPass,
+                    Shadow2dNode::Shadow2dLightPass,
+                    Shadow2dNode::Shadow2dMainPass,
+                    Node2d::Bloom,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+
+        render_app
+            .init_resource::<Shadow2dLightPassPipeline>()
+            .init_resource::<Shadow2dMainPassPipeline>();
+    }
+}
+
+/// Closed outline of a caster in world space, whose edges are extruded into shadows.
+#[derive(Component)]
+pub struct ExtractedCasterOutline2d {
+    pub vertices: Vec<Vec2>,
+}
+
+type ExtractOutlineShape = (
+    Entity,
+    &'static ShadowCasterShape2d,
+    &'static GlobalTransform,
+    Option<&'static InheritedVisibility>,
+);
+
+type ExtractOutlineSprite = (
+    Entity,
+    &'static Sprite,
+    &'static Handle<Image>,
+    Option<&'static TextureAtlas>,
+    &'static GlobalTransform,
+    &'static InheritedVisibility,
+);
+
+type OutlineSpriteFilter = (
+    With<ShadowCaster2d>,
+    Without<ShadowCasterShape2d>,
+    Without<TranslucentShadowCaster2d>,
+);
+
+/// Sprites are approximated by their rectangles, use [`ShadowCasterShape2d`]
+/// for tighter silhouettes. Translucent casters don't cast shadows in this approach.
+pub fn extract_caster_outlines(
+    mut commands: Commands,
+    shapes_query: Extract<Query<ExtractOutlineShape>>,
+    sprites_query: Extract<Query<ExtractOutlineSprite, OutlineSpriteFilter>>,
+    images: Extract<Res<Assets<Image>>>,
+    atlas_layouts: Extract<Res<Assets<TextureAtlasLayout>>>,
+) {
+    let shapes = shapes_query
+        .iter()
+        .filter(|(.., visibility)| {
+            visibility
+                .copied()
+                .unwrap_or(InheritedVisibility::VISIBLE)
+                .get()
+        })
+        .map(|(entity, shape, transform, _)| {
+            (
+                entity,
+                ExtractedCasterOutline2d {
+                    vertices: shape
+                        .outline()
+                        .into_iter()
+                        .map(|vertex| transform.transform_point(vertex.extend(0.)).truncate())
+                        .collect(),
+                },
+            )
+        });
+
+    let sprites = sprites_query
+        .iter()
+        .filter(|(.., visibility)| visibility.get())
+        .filter_map(|(entity, sprite, image, atlas, transform, _)| {
+            let atlas_rect = atlas.and_then(|atlas| {
+                atlas_layouts
+                    .get(&atlas.layout)
+                    .and_then(|layout| layout.textures.get(atlas.index))
+            });
+            let size = sprite
+                .custom_size
+                .or_else(|| sprite.rect.or(atlas_rect.copied()).map(|rect| rect.size()))
+                .or_else(|| images.get(image).map(|image| image.size_f32()))?;
+            let anchor = sprite.anchor.as_vec();
+
+            Some((
+                entity,
+                ExtractedCasterOutline2d {
+                    vertices: [
+                        Vec2::new(-0.5, -0.5),
+                        Vec2::new(0.5, -0.5),
+                        Vec2::new(0.5, 0.5),
+                        Vec2::new(-0.5, 0.5),
+                    ]
+                    .into_iter()
+                    .map(|corner| {
+                        transform
+                            .transform_point(((corner - anchor) * size).extend(0.))
+                            .truncate()
+                    })
+                    .collect(),
+                },
+            ))
+        });
+
+    commands.insert_or_spawn_batch(shapes.chain(sprites).collect::<Vec<_>>());
+}
+
+/// Edges of all casters, start in xy and end in zw, drawn as instances.
+#[derive(Resource)]
+pub struct ShadowEdgeBuffer {
+    edges: BufferVec<Vec4>,
+}
+
+impl Default for ShadowEdgeBuffer {
+    fn default() -> Self {
+        Self {
+            edges: BufferVec::new(BufferUsages::VERTEX),
+        }
+    }
+}
+
+impl ShadowEdgeBuffer {
+    #[inline]
+    pub fn edges(&self) -> &BufferVec<Vec4> {
+        &self.edges
+    }
+}
+
+pub fn prepare_shadow_edges(
+    outlines_query: Query<&ExtractedCasterOutline2d>,
+    mut edge_buffer: ResMut<ShadowEdgeBuffer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    edge_buffer.edges.clear();
+    for outline in &outlines_query {
+        let vertices = &outline.vertices;
+        for (i, start) in vertices.iter().enumerate() {
+            let end = vertices[(i + 1) % vertices.len()];
+            edge_buffer
+                .edges
+                .push(Vec4::new(start.x, start.y, end.x, end.y));
+        }
+    }
+    edge_buffer
+        .edges
+        .write_buffer(&render_device, &render_queue);
+}
+
+#[derive(Component)]
+pub struct LightBuffer2d {
+    pub light: ColorAttachment,
+    /// Cleared for each light, and marked by the shadows of that light.
+    pub stencil: CachedTexture,
+}
+
+pub fn prepare_light_buffers(
+    mut commands: Commands,
+    main_views_query: Query<(Entity, &ExtractedView, &Lighting2dApproach), With<ViewTarget>>,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+) {
+    for (main_view_entity, main_view, approach) in &main_views_query {
+        if *approach != Lighting2dApproach::Geometry {
+            continue;
+        }
+
+        let size = Extent3d {
+            width: main_view.viewport.z.max(1),
+            height: main_view.viewport.w.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let light = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("light_buffer_2d"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: LIGHT_BUFFER_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+
+        let stencil = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("shadow_stencil_2d"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: SHADOW_STENCIL_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+        );
+
+        commands.entity(main_view_entity).insert(LightBuffer2d {
+            light: ColorAttachment::new(light, None, Some(Color::NONE)),
+            stencil,
+        });
+    }
+}
+
+/// Lights are drawn one by one, with their index passed through the [`NumberBuffer`],
+/// which needs to hold as many numbers as there are lights.
+pub fn prepare_light_indices(
+    main_views_query: Query<(&GpuLights2d, &Lighting2dApproach)>,
+    mut number_buffer: ResMut<NumberBuffer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let max_lights = main_views_query
+        .iter()
+        .filter(|(_, approach)| **approach == Lighting2dApproach::Geometry)
+        .map(|(lights, _)| {
+            lights
+                .point_lights_len()
+                .max(lights.directional_lights_len())
+        })
+        .max()
+        .unwrap_or_default();
+
+    number_buffer.reserve(max_lights, &render_device, &render_queue);
+}
@@ -0,0 +1,301 @@
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::{
+        system::Resource,
+        world::{FromWorld, World},
+    },
+    render::{
+        render_resource::{
+            AddressMode, BindGroupLayout, BindGroupLayoutEntries, BlendComponent, BlendFactor,
+            BlendOperation, BlendState, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            CompareFunction, DepthBiasState, DepthStencilState, FilterMode, FragmentState,
+            MultisampleState, PipelineCache, PrimitiveState, RenderPipelineDescriptor, Sampler,
+            SamplerBindingType, SamplerDescriptor, ShaderDefVal, ShaderStages, StencilFaceState,
+            StencilOperation, StencilState, TextureFormat, TextureSampleType, VertexBufferLayout,
+            VertexFormat, VertexState, VertexStepMode,
+        },
+        renderer::RenderDevice,
+        texture::BevyDefault,
+        view::{ViewTarget, ViewUniform},
+    },
+};
+
+use bevy::render::render_resource::binding_types as binding;
+
+use crate::render::light::{
+    falloff_curves_shader_def, GpuAmbientLight2d, GpuDirectionalLight2d, GpuPointLight2d,
+};
+
+use super::{
+    LIGHT_BUFFER_FORMAT, SHADOW_LIGHT_PASS_SHADER, SHADOW_MAIN_PASS_SHADER, SHADOW_STENCIL_FORMAT,
+    SHADOW_VOLUME_SHADER,
+};
+
+fn get_stencil_state(face: StencilFaceState, write_mask: u32) -> DepthStencilState {
+    DepthStencilState {
+        format: SHADOW_STENCIL_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask,
+        },
+        bias: DepthBiasState::default(),
+    }
+}
+
+/// Draws the shadow volumes of a light into the stencil buffer, then the light itself
+/// where the stencil buffer is untouched.
+#[derive(Resource)]
+pub struct Shadow2dLightPassPipeline {
+    pub point_volume_id: CachedRenderPipelineId,
+    pub directional_volume_id: CachedRenderPipelineId,
+    pub point_light_id: CachedRenderPipelineId,
+    pub directional_light_id: CachedRenderPipelineId,
+    pub volume_layout: BindGroupLayout,
+    pub light_layout: BindGroupLayout,
+    pub sampler: Sampler,
+}
+
+impl FromWorld for Shadow2dLightPassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let volume_layout = render_device.create_bind_group_layout(
+            "light_2d_shadow_volume_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX,
+                (
+                    // View
+                    binding::uniform_buffer::<ViewUniform>(true),
+                    // Point lights
+                    binding::storage_buffer_read_only::<Vec<GpuPointLight2d>>(false),
+                    // Directional lights
+                    binding::storage_buffer_read_only::<Vec<GpuDirectionalLight2d>>(false),
+                    // Light index
+                    binding::uniform_buffer::<u32>(true),
+                ),
+            ),
+        );
+
+        let light_layout = render_device.create_bind_group_layout(
+            "light_2d_light_pass_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // View
+                    binding::uniform_buffer::<ViewUniform>(true),
+                    // Point lights
+                    binding::storage_buffer_read_only::<Vec<GpuPointLight2d>>(false),
+                    // Directional lights
+                    binding::storage_buffer_read_only::<Vec<GpuDirectionalLight2d>>(false),
+                    // Light index
+                    binding::uniform_buffer::<u32>(true),
+                    // Light cookies
+                    binding::texture_2d_array(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                    // Falloff curves, see `falloff_curves_shader_def`
+                    binding::storage_buffer_read_only::<Vec<f32>>(false),
+                    // Surface normals
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface material
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("light_2d_light_pass_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let volume_descriptor = |label: &'static str, shader_defs: Vec<ShaderDefVal>| {
+            RenderPipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![volume_layout.clone()],
+                push_constant_ranges: vec![],
+                vertex: VertexState {
+                    shader: SHADOW_VOLUME_SHADER,
+                    shader_defs: shader_defs.clone(),
+                    entry_point: "vertex".into(),
+                    buffers: vec![VertexBufferLayout::from_vertex_formats(
+                        VertexStepMode::Instance,
+                        [VertexFormat::Float32x4],
+                    )],
+                },
+                primitive: PrimitiveState::default(),
+                // Every shadow covering a pixel increments the stencil value.
+                depth_stencil: Some(get_stencil_state(
+                    StencilFaceState {
+                        compare: CompareFunction::Always,
+                        fail_op: StencilOperation::Keep,
+                        depth_fail_op: StencilOperation::Keep,
+                        pass_op: StencilOperation::IncrementClamp,
+                    },
+                    !0,
+                )),
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    shader: SHADOW_VOLUME_SHADER,
+                    shader_defs,
+                    entry_point: "fragment".into(),
+                    targets: vec![Some(ColorTargetState {
+                        format: LIGHT_BUFFER_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::empty(),
+                    })],
+                }),
+            }
+        };
+
+        let light_descriptor = |label: &'static str, entry_point: &'static str| {
+            RenderPipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![light_layout.clone()],
+                push_constant_ranges: vec![],
+                vertex: fullscreen_shader_vertex_state(),
+                primitive: PrimitiveState::default(),
+                // Only lit where no shadow was drawn.
+                depth_stencil: Some(get_stencil_state(
+                    StencilFaceState {
+                        compare: CompareFunction::Equal,
+                        fail_op: StencilOperation::Keep,
+                        depth_fail_op: StencilOperation::Keep,
+                        pass_op: StencilOperation::Keep,
+                    },
+                    0,
+                )),
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    shader: SHADOW_LIGHT_PASS_SHADER,
+                    shader_defs: vec![falloff_curves_shader_def(6)],
+                    entry_point: entry_point.into(),
+                    targets: vec![Some(ColorTargetState {
+                        format: LIGHT_BUFFER_FORMAT,
+                        blend: Some(BlendState {
+                            color: BlendComponent {
+                                src_factor: BlendFactor::One,
+                                dst_factor: BlendFactor::One,
+                                operation: BlendOperation::Add,
+                            },
+                            alpha: BlendComponent::OVER,
+                        }),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+            }
+        };
+
+        Self {
+            point_volume_id: pipeline_cache.queue_render_pipeline(volume_descriptor(
+                "light_2d_point_shadow_volume_pipeline",
+                vec![],
+            )),
+            directional_volume_id: pipeline_cache.queue_render_pipeline(volume_descriptor(
+                "light_2d_directional_shadow_volume_pipeline",
+                vec!["DIRECTIONAL".into()],
+            )),
+            point_light_id: pipeline_cache.queue_render_pipeline(light_descriptor(
+                "light_2d_point_light_pipeline",
+                "point_light",
+            )),
+            directional_light_id: pipeline_cache.queue_render_pipeline(light_descriptor(
+                "light_2d_directional_light_pipeline",
+                "directional_light",
+            )),
+            volume_layout,
+            light_layout,
+            sampler,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Shadow2dMainPassPipeline {
+    pub cached_id: CachedRenderPipelineId,
+    pub hdr_cached_id: CachedRenderPipelineId,
+    pub main_pass_layout: BindGroupLayout,
+    pub main_texture_sampler: Sampler,
+}
+
+impl FromWorld for Shadow2dMainPassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let main_pass_layout = render_device.create_bind_group_layout(
+            "light_2d_geometry_main_pass_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // Main texture
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                    // Light buffer
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Ambient light
+                    binding::uniform_buffer::<GpuAmbientLight2d>(false),
+                    // Surface material
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface emissive
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                ),
+            ),
+        );
+
+        let main_texture_sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("main_texture_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Writes into the view target, which is `Rgba16Float` for hdr cameras.
+        let descriptor = |label: &'static str, format: TextureFormat| RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: vec![main_pass_layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: SHADOW_MAIN_PASS_SHADER,
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        Self {
+            cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_geometry_main_pass_pipeline",
+                TextureFormat::bevy_default(),
+            )),
+            hdr_cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_geometry_main_pass_hdr_pipeline",
+                ViewTarget::TEXTURE_FORMAT_HDR,
+            )),
+            main_pass_layout,
+            main_texture_sampler,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::view::View
+#import bevy_incandescent::{
+    lighting::{
+        get_cookie_uv, get_elliptical_rel, get_normal_shading, get_point_light_falloff,
+        get_specular_shading, screen_to_world, NO_LIGHT_COOKIE,
+    },
+    math::{get_sector_falloff, is_point_inside_sector},
+    types::{DirectionalLight2d, PointLight2d},
+}
+
+@group(0) @binding(0)
+var<uniform> main_view: View;
+
+@group(0) @binding(1)
+var<storage> point_lights: array<PointLight2d>;
+
+@group(0) @binding(2)
+var<storage> directional_lights: array<DirectionalLight2d>;
+
+@group(0) @binding(3)
+var<uniform> light_index: u32;
+
+@group(0) @binding(4)
+var light_cookies: texture_2d_array<f32>;
+
+@group(0) @binding(5)
+var light_cookie_sampler: sampler;
+
+@group(0) @binding(7)
+var surface_normal: texture_2d<f32>;
+
+@group(0) @binding(8)
+var surface_material: texture_2d<f32>;
+
+@group(0) @binding(9)
+var surface_sampler: sampler;
+
+fn get_cookie_color(light: PointLight2d, rel_ss: vec2f, range_ss: f32) -> vec3f {
+    if light.cookie_index == NO_LIGHT_COOKIE {
+        return vec3f(1.);
+    }
+
+    let uv = get_cookie_uv(rel_ss, range_ss, light.rotation);
+    let cookie = textureSampleLevel(light_cookies, light_cookie_sampler, uv, light.cookie_index, 0.);
+    return cookie.rgb * cookie.a;
+}
+
+// Shadows are already cut out by the stencil buffer.
+@fragment
+fn point_light(in: FullscreenVertexOutput) -> @location(0) vec4f {
+    let screen_size = 2. * vec2f(main_view.inverse_projection[0][0], main_view.inverse_projection[1][1]);
+    let px = in.uv * screen_size;
+    let surface = textureSample(surface_normal, surface_sampler, in.uv);
+    let material = textureSample(surface_material, surface_sampler, in.uv);
+
+    let light = point_lights[light_index];
+    let light_pos_ss = light.position_ss * screen_size;
+    let light_range_ss = max(light.range_ss, 0.) * screen_size.x;
+    let light_radius_ss = max(light.radius_ss, 0.) * screen_size.x;
+    // Sectors are defined in world space where y points upwards.
+    let rel_ws = screen_to_world(px - light_pos_ss, main_view.view);
+    let rel_range_ws = get_elliptical_rel(rel_ws, light.range_scale, light.range_rotation);
+
+    if !is_point_inside_sector(rel_range_ws, vec2f(0.), light_range_ss, light.angles) {
+        return vec4f(0.);
+    }
+
+    let light_height_ss = max(light.height_ss, 0.) * screen_size.x;
+    let attenuation = get_point_light_falloff(light, length(rel_range_ws), light_radius_ss, light_range_ss)
+                      * get_sector_falloff(rel_range_ws, light.angles, light.inner_extent)
+                      * (get_normal_shading(surface, -rel_ws, light_height_ss)
+                         + get_specular_shading(surface, material, -rel_ws, light_height_ss));
+    let cookie_color = get_cookie_color(light, rel_range_ws * vec2f(1., -1.), light_range_ss);
+    return vec4f(light.color.rgb * light.intensity * attenuation * cookie_color, 0.);
+}
+
+@fragment
+fn directional_light(in: FullscreenVertexOutput) -> @location(0) vec4f {
+    let light = directional_lights[light_index];
+    return vec4f(light.color.rgb * light.intensity, 0.);
+}
@@ -0,0 +1,32 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_incandescent::types::AmbientLight2d
+
+@group(0) @binding(0)
+var main_tex: texture_2d<f32>;
+
+@group(0) @binding(1)
+var main_tex_sampler: sampler;
+
+@group(0) @binding(2)
+var light_buffer: texture_2d<f32>;
+
+@group(0) @binding(3)
+var<uniform> ambient_light: AmbientLight2d;
+
+@group(0) @binding(4)
+var surface_material: texture_2d<f32>;
+
+@group(0) @binding(5)
+var surface_emissive: texture_2d<f32>;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
+    let material = textureSample(surface_material, main_tex_sampler, in.uv);
+    let color = textureSample(light_buffer, main_tex_sampler, in.uv).rgb;
+
+    let receiver = max(material.b, 0.);
+    let ambient = mix(vec3f(1.), ambient_light.color.rgb * ambient_light.intensity, saturate(receiver));
+    return textureSample(main_tex, main_tex_sampler, in.uv) * vec4f(ambient, 1.)
+           + vec4f(color * receiver, 0.)
+           + vec4f(textureSample(surface_emissive, main_tex_sampler, in.uv).rgb, 0.);
+}
@@ -0,0 +1,63 @@
+#import bevy_render::view::View
+#import bevy_incandescent::types::{DirectionalLight2d, PointLight2d}
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+@group(0) @binding(1)
+var<storage> point_lights: array<PointLight2d>;
+
+@group(0) @binding(2)
+var<storage> directional_lights: array<DirectionalLight2d>;
+
+@group(0) @binding(3)
+var<uniform> light_index: u32;
+
+// Far enough in NDC to leave the screen from anywhere on it.
+const POINT_LIGHT_EXTRUSION: f32 = 100.;
+
+// Each instance is an edge with its start in xy and end in zw, extruded away from the light
+// into a quad.
+@vertex
+fn vertex(@builtin(vertex_index) index: u32, @location(0) edge: vec4f) -> @builtin(position) vec4f {
+    // x picks the end of the edge, y whether it is extruded.
+    var corners = array<vec2u, 6>(
+        vec2u(0u, 0u),
+        vec2u(1u, 0u),
+        vec2u(1u, 1u),
+        vec2u(0u, 0u),
+        vec2u(1u, 1u),
+        vec2u(0u, 1u),
+    );
+    let corner = corners[index];
+
+    let vertex_ws = select(edge.xy, edge.zw, corner.x == 1u);
+    let vertex_clip = view.view_proj * vec4f(vertex_ws, 0., 1.);
+    let vertex_ndc = vertex_clip.xy / vertex_clip.w;
+    if corner.y == 0u {
+        return vec4f(vertex_ndc, 0., 1.);
+    }
+
+#ifdef DIRECTIONAL
+    let light = directional_lights[light_index];
+    let screen_size = 2. * vec2f(view.inverse_projection[0][0], view.inverse_projection[1][1]);
+    let shadow_length_ss = max(light.shadow_length_ss, 0.) * screen_size.x;
+    // From pixels where y points downwards to NDC.
+    let offset_ndc = light.direction_ss * shadow_length_ss * vec2f(2., -2.) / screen_size;
+#else
+    let light = point_lights[light_index];
+    let light_ndc = (light.position_ss * 2. - 1.) * vec2f(1., -1.);
+    let away = vertex_ndc - light_ndc;
+    var offset_ndc = vec2f(0.);
+    if length(away) > 0.0001 {
+        offset_ndc = normalize(away) * POINT_LIGHT_EXTRUSION;
+    }
+#endif
+
+    return vec4f(vertex_ndc + offset_ndc, 0., 1.);
+}
+
+@fragment
+fn fragment() -> @location(0) vec4f {
+    return vec4f(0.);
+}
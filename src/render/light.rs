@@ -1,12 +1,84 @@
 use bevy::{
     ecs::{component::Component, system::Resource},
-    math::{Vec2, Vec4},
+    math::{Mat4, Quat, Vec2, Vec3, Vec4, Vec4Swizzles},
     render::{
-        render_resource::{BindingResource, DynamicUniformBuffer, GpuArrayBuffer, ShaderType},
+        render_resource::{
+            BindingResource, DynamicUniformBuffer, GpuArrayBuffer, ShaderDefVal, ShaderType,
+        },
         renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
     },
 };
 
+/// Tells `get_point_light_falloff` in `bevy_incandescent::lighting` where the lighting pass
+/// binds [`GpuLights2d::falloff_curves_binding`] in group 0.
+pub fn falloff_curves_shader_def(binding: u32) -> ShaderDefVal {
+    ShaderDefVal::UInt("FALLOFF_CURVES_BINDING".into(), binding)
+}
+
+/// Converts lights from world space into the screen space of a view.
+///
+/// Shaders turn screen space back into world units by multiplying positions by
+/// [`Self::view_size`], and lengths by its x component. So lengths are the proportion
+/// of the width of the view, which keeps lights circular in world space whatever the
+/// zoom, the scaling mode or the aspect ratio.
+#[derive(Clone, Copy)]
+pub struct LightScreenSpace {
+    view_proj: Mat4,
+    view_pos_ws: Vec3,
+    view_right_ws: Vec3,
+    view_rotation_inv: Quat,
+    view_size: Vec2,
+}
+
+impl LightScreenSpace {
+    pub fn new(view: &ExtractedView) -> Self {
+        Self {
+            view_proj: view
+                .view_projection
+                .unwrap_or_else(|| view.projection * view.transform.compute_matrix().inverse()),
+            view_pos_ws: view.transform.translation(),
+            view_right_ws: view.transform.right(),
+            view_rotation_inv: view.transform.compute_transform().rotation.inverse(),
+            view_size: 2. / Vec2::new(view.projection.x_axis[0], view.projection.y_axis[1]),
+        }
+    }
+
+    /// The size of the view in world units, as computed by the shaders
+    /// from the inverse projection.
+    #[inline]
+    pub fn view_size(&self) -> Vec2 {
+        self.view_size
+    }
+
+    /// Converts a position into `[0, 1]`, where y points downwards.
+    pub fn position(&self, position: Vec3) -> Vec2 {
+        let mut position_ndc = self.project(position);
+        position_ndc.y = -position_ndc.y;
+        (position_ndc + 1.) / 2.
+    }
+
+    /// Converts a length into the proportion of the width of the view.
+    ///
+    /// It is measured along the x axis of the camera from the camera itself,
+    /// so it doesn't depend on where the origin of the viewport is.
+    pub fn length(&self, length: f32) -> f32 {
+        let end_ndc = self.project(self.view_right_ws * length + self.view_pos_ws);
+        (end_ndc.x - self.project(self.view_pos_ws).x) / 2.
+    }
+
+    /// Rotates a direction into the camera, so that lighting follows it when it rotates.
+    /// y points downwards.
+    pub fn direction(&self, direction: Vec2) -> Vec2 {
+        (self.view_rotation_inv * direction.extend(0.)).truncate() * Vec2::new(1., -1.)
+    }
+
+    fn project(&self, position: Vec3) -> Vec2 {
+        let position_clip = self.view_proj * position.extend(1.);
+        position_clip.xy() / position_clip.w
+    }
+}
+
 #[derive(ShaderType)]
 pub struct GpuAmbientLight2d {
     pub color: Vec4,
@@ -43,13 +115,46 @@ pub struct GpuPointLight2d {
     pub position_ss: Vec2,
     pub radius_ss: f32,
     pub range_ss: f32,
+    pub height_ss: f32,
     pub color: Vec4,
     pub angles: [f32; 2],
+    pub inner_extent: f32,
+    pub cookie_index: u32,
+    pub rotation: f32,
+    pub falloff: u32,
+    pub falloff_curve_offset: u32,
+    pub falloff_curve_len: u32,
+    pub range_scale: Vec2,
+    pub range_rotation: f32,
+}
+
+#[derive(ShaderType, Clone)]
+pub struct GpuLineLight2d {
+    pub intensity: f32,
+    pub start_ss: Vec2,
+    pub end_ss: Vec2,
+    pub thickness_ss: f32,
+    pub range_ss: f32,
+    pub color: Vec4,
+}
+
+#[derive(ShaderType, Clone)]
+pub struct GpuDirectionalLight2d {
+    pub color: Vec4,
+    pub intensity: f32,
+    pub direction_ss: Vec2,
+    pub shadow_length_ss: f32,
 }
 
 #[derive(Component)]
 pub struct GpuLights2d {
     point_lights: GpuArrayBuffer<GpuPointLight2d>,
+    line_lights: GpuArrayBuffer<GpuLineLight2d>,
+    directional_lights: GpuArrayBuffer<GpuDirectionalLight2d>,
+    falloff_curves: GpuArrayBuffer<f32>,
+    falloff_curves_len: u32,
+    point_lights_len: u32,
+    directional_lights_len: u32,
 }
 
 impl GpuLights2d {
@@ -57,12 +162,50 @@ impl GpuLights2d {
     pub fn new(render_device: &RenderDevice) -> Self {
         Self {
             point_lights: GpuArrayBuffer::new(render_device),
+            line_lights: GpuArrayBuffer::new(render_device),
+            directional_lights: GpuArrayBuffer::new(render_device),
+            falloff_curves: GpuArrayBuffer::new(render_device),
+            falloff_curves_len: 0,
+            point_lights_len: 0,
+            directional_lights_len: 0,
         }
     }
 
     #[inline]
     pub fn add_point_light(&mut self, light: GpuPointLight2d) {
         self.point_lights.push(light);
+        self.point_lights_len += 1;
+    }
+
+    #[inline]
+    pub fn add_line_light(&mut self, light: GpuLineLight2d) {
+        self.line_lights.push(light);
+    }
+
+    #[inline]
+    pub fn add_directional_light(&mut self, light: GpuDirectionalLight2d) {
+        self.directional_lights.push(light);
+        self.directional_lights_len += 1;
+    }
+
+    /// Returns the offset of the curve in the buffer.
+    pub fn add_falloff_curve(&mut self, curve: &[f32]) -> u32 {
+        let offset = self.falloff_curves_len;
+        for sample in curve {
+            self.falloff_curves.push(*sample);
+        }
+        self.falloff_curves_len += curve.len() as u32;
+        offset
+    }
+
+    #[inline]
+    pub fn point_lights_len(&self) -> u32 {
+        self.point_lights_len
+    }
+
+    #[inline]
+    pub fn directional_lights_len(&self) -> u32 {
+        self.directional_lights_len
     }
 
     #[inline]
@@ -70,13 +213,39 @@ impl GpuLights2d {
         self.point_lights.binding().unwrap()
     }
 
+    #[inline]
+    pub fn line_lights_binding(&self) -> BindingResource<'_> {
+        self.line_lights.binding().unwrap()
+    }
+
+    #[inline]
+    pub fn directional_lights_binding(&self) -> BindingResource<'_> {
+        self.directional_lights.binding().unwrap()
+    }
+
+    #[inline]
+    pub fn falloff_curves_binding(&self) -> BindingResource<'_> {
+        self.falloff_curves.binding().unwrap()
+    }
+
     #[inline]
     pub fn clear(&mut self) {
         self.point_lights.clear();
+        self.line_lights.clear();
+        self.directional_lights.clear();
+        self.falloff_curves.clear();
+        self.falloff_curves_len = 0;
+        self.point_lights_len = 0;
+        self.directional_lights_len = 0;
     }
 
     #[inline]
     pub fn write_buffers(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
         self.point_lights.write_buffer(render_device, render_queue);
+        self.line_lights.write_buffer(render_device, render_queue);
+        self.directional_lights
+            .write_buffer(render_device, render_queue);
+        self.falloff_curves
+            .write_buffer(render_device, render_queue);
     }
 }
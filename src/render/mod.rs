@@ -2,41 +2,64 @@ use std::marker::PhantomData;
 
 use bevy::{
     app::{App, Plugin, PostUpdate},
-    asset::{load_internal_asset, Handle},
+    asset::{load_internal_asset, AssetId, Handle},
+    core_pipeline::core_2d::{
+        graph::{Core2d, Node2d},
+        Camera2d,
+    },
     ecs::{
         component::Component,
         entity::Entity,
         query::With,
         schedule::IntoSystemConfigs,
-        system::{Commands, Query, Res},
+        system::{Commands, Query, Res, SystemParam},
     },
-    math::{Vec3, Vec4Swizzles},
+    math::{EulerRot, Vec2, Vec3},
     render::{
+        camera::Camera,
         color::Color,
         extract_resource::ExtractResourcePlugin,
+        render_graph::RenderGraphApp,
         render_resource::{Shader, ShaderType},
         renderer::{RenderDevice, RenderQueue},
+        texture::Image,
         view::{ExtractedView, ViewTarget, VisibilitySystems, VisibleEntities},
         Extract, ExtractSchedule, Render, RenderApp, RenderSet,
     },
     transform::components::GlobalTransform,
+    utils::warn_once,
 };
 
 use crate::{
-    ecs::{AmbientLight2d, PointLight2d, SpotLight2d},
+    ecs::{
+        AmbientLight2d, DirectionalLight2d, LightFalloff2d, Lighting2dApproach, LineLight2d,
+        PointLight2d, SpotLight2d,
+    },
     render::light::{GpuAmbientLight2d, GpuAmbientLight2dBuffer},
 };
 
 use self::{
-    light::{GpuLights2d, GpuPointLight2d},
+    caster::{Caster2dBuffers, Caster2dPipeline},
+    cookie::{LightCookieBlitPipeline, LightCookieStorage},
+    light::{
+        GpuDirectionalLight2d, GpuLights2d, GpuLineLight2d, GpuPointLight2d, LightScreenSpace,
+    },
+    surface::{Surface2dBuffer, Surface2dPass, Surface2dPassNode, Surface2dPipeline},
     universal_buffers::{BooleanBuffer, NumberBuffer},
 };
 
+pub mod caster;
 #[cfg(feature = "catalinzz")]
 pub mod catalinzz;
+pub mod cookie;
+#[cfg(feature = "geometry")]
+pub mod geometry;
 pub mod light;
+#[cfg(feature = "radiance_cascades")]
+pub mod radiance_cascades;
 #[cfg(feature = "ray_marching")]
 pub mod ray_marching;
+pub mod surface;
 pub mod universal_buffers;
 pub mod visibility;
 
@@ -44,6 +67,12 @@ pub const HASH_SHADER: Handle<Shader> = Handle::weak_from_u128(94897465132296841
 pub const MATH_SHADER: Handle<Shader> = Handle::weak_from_u128(45341649741532875412078496512304512);
 pub const LIGHTING_SHADER: Handle<Shader> = Handle::weak_from_u128(1351654315646451321546531153891);
 pub const TYPES_SHADER: Handle<Shader> = Handle::weak_from_u128(5798645318564312354689689451005103);
+pub const LIGHT_COOKIE_BLIT_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(8413516849631568413206841385124636);
+pub const SURFACE_SHADER: Handle<Shader> = Handle::weak_from_u128(3541865413218974651321846513);
+pub const CASTER_SHAPE_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(7516835416874321035468435187642);
+pub const OCCLUDER_SHADER: Handle<Shader> = Handle::weak_from_u128(6813548613254987465132054687);
 
 pub struct IncandescentRenderPlugin;
 
@@ -62,26 +91,111 @@ impl Plugin for IncandescentRenderPlugin {
 
         load_internal_asset!(app, TYPES_SHADER, "shaders/types.wgsl", Shader::from_wgsl);
 
+        load_internal_asset!(
+            app,
+            LIGHT_COOKIE_BLIT_SHADER,
+            "shaders/cookie_blit.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            SURFACE_SHADER,
+            "shaders/surface.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            CASTER_SHAPE_SHADER,
+            "shaders/caster_shape.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            OCCLUDER_SHADER,
+            "shaders/occluder.wgsl",
+            Shader::from_wgsl
+        );
+
         app.add_plugins((
             ExtractResourcePlugin::<AmbientLight2d>::default(),
             #[cfg(feature = "catalinzz")]
             catalinzz::CatalinzzApproachPlugin,
             #[cfg(feature = "ray_marching")]
             ray_marching::RayMarchingApproachPlugin,
+            #[cfg(feature = "geometry")]
+            geometry::GeometryApproachPlugin,
+            #[cfg(feature = "radiance_cascades")]
+            radiance_cascades::RadianceCascadesPlugin,
         ))
         .init_resource::<AmbientLight2d>()
         .register_type::<AmbientLight2d>()
         .add_systems(
             PostUpdate,
-            visibility::calc_light_bounds.in_set(VisibilitySystems::CalculateBounds),
+            (
+                visibility::calc_light_bounds.in_set(VisibilitySystems::CalculateBounds),
+                caster::triangulate_caster_shapes,
+            ),
         );
 
         let render_app = app.sub_app_mut(RenderApp);
 
         render_app
             .init_resource::<GpuAmbientLight2dBuffer>()
-            .add_systems(ExtractSchedule, extract_lights)
-            .add_systems(Render, prepare_lights.in_set(RenderSet::Prepare));
+            .init_resource::<Surface2dBuffer>()
+            .init_resource::<Caster2dBuffers>()
+            .add_systems(
+                ExtractSchedule,
+                (
+                    extract_lights,
+                    extract_lighting_approaches,
+                    cookie::extract_light_cookie_events,
+                    surface::extract_surfaces,
+                    caster::extract_casters,
+                    caster::extract_caster_views,
+                ),
+            )
+            .add_systems(
+                Render,
+                (
+                    (cookie::prepare_light_cookies, prepare_lights).chain(),
+                    surface::prepare_surfaces,
+                )
+                    .in_set(RenderSet::Prepare),
+            )
+            .add_systems(
+                Render,
+                caster::prepare_casters.in_set(RenderSet::PrepareBindGroups),
+            )
+            .add_render_graph_node::<Surface2dPassNode>(Core2d, Surface2dPass)
+            .add_render_graph_edge(Core2d, Node2d::MainPass, Surface2dPass);
+
+        #[cfg(feature = "catalinzz")]
+        render_app.add_render_graph_edge(
+            Core2d,
+            Surface2dPass,
+            catalinzz::graph::Shadow2dNode::Shadow2dMainPass,
+        );
+        #[cfg(feature = "ray_marching")]
+        render_app.add_render_graph_edge(
+            Core2d,
+            Surface2dPass,
+            ray_marching::graph::Shadow2dNode::Shadow2dMainPass,
+        );
+        #[cfg(feature = "geometry")]
+        render_app.add_render_graph_edge(
+            Core2d,
+            Surface2dPass,
+            geometry::graph::Shadow2dNode::Shadow2dLightPass,
+        );
+        #[cfg(feature = "radiance_cascades")]
+        render_app.add_render_graph_edge(
+            Core2d,
+            Surface2dPass,
+            radiance_cascades::graph::RadianceCascadesNode::CascadePass,
+        );
     }
 
     fn finish(&self, app: &mut App) {
@@ -89,7 +203,11 @@ impl Plugin for IncandescentRenderPlugin {
 
         render_app
             .init_resource::<NumberBuffer>()
-            .init_resource::<BooleanBuffer>();
+            .init_resource::<BooleanBuffer>()
+            .init_resource::<LightCookieBlitPipeline>()
+            .init_resource::<LightCookieStorage>()
+            .init_resource::<Surface2dPipeline>()
+            .init_resource::<Caster2dPipeline>();
     }
 }
 
@@ -113,20 +231,83 @@ impl<S: ShaderType> DynamicUniformIndex<S> {
     }
 }
 
-#[derive(Component, Clone, Copy)]
+pub const LIGHT_FALLOFF_LINEAR: u32 = 0;
+pub const LIGHT_FALLOFF_SMOOTH: u32 = 1;
+pub const LIGHT_FALLOFF_INVERSE_SQUARE: u32 = 2;
+pub const LIGHT_FALLOFF_CUSTOM: u32 = 3;
+
+#[derive(Component, Clone)]
 pub struct ExtractedPointLight2d {
     pub id: u32,
     pub color: Color,
     pub intensity: f32,
     pub range: f32,
     pub radius: f32,
+    pub height: f32,
     pub spot_light_angles: [f32; 2],
+    pub spot_light_inner_extent: f32,
+    pub cookie: Option<AssetId<Image>>,
+    pub rotation: f32,
+    pub falloff: LightFalloff2d,
+    /// The elliptical range along its axes divided by `range`, `Vec2::ONE` for circular lights.
+    pub range_scale: Vec2,
+    /// The rotation of the axes of the elliptical range in world space.
+    pub range_rotation: f32,
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct ExtractedLineLight2d {
+    pub color: Color,
+    pub intensity: f32,
+    pub start: Vec3,
+    pub end: Vec3,
+    pub thickness: f32,
+    pub range: f32,
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct ExtractedDirectionalLight2d {
+    pub color: Color,
+    pub intensity: f32,
+    pub direction: Vec2,
+    pub shadow_length: f32,
+}
+
+type ExtractLightingCamera = (Entity, &'static Camera, Option<&'static Lighting2dApproach>);
+
+/// Every active 2d camera gets a [`Lighting2dApproach`], so the nodes and systems
+/// of the other approaches skip its view.
+pub fn extract_lighting_approaches(
+    mut commands: Commands,
+    cameras_query: Extract<Query<ExtractLightingCamera, With<Camera2d>>>,
+) {
+    commands.insert_or_spawn_batch(
+        cameras_query
+            .iter()
+            .filter(|(_, camera, _)| camera.is_active)
+            .map(|(entity, _, approach)| {
+                let approach = approach.copied().unwrap_or_default();
+                if approach.is_enabled() {
+                    (entity, approach)
+                } else {
+                    warn_once!(
+                        "{:?} is not enabled by cargo features, using {:?} instead.",
+                        approach,
+                        Lighting2dApproach::default()
+                    );
+                    (entity, Lighting2dApproach::default())
+                }
+            })
+            .collect::<Vec<_>>(),
+    );
 }
 
 pub fn extract_lights(
     mut commands: Commands,
     point_lights_query: Extract<Query<(Entity, &PointLight2d, &GlobalTransform)>>,
     spot_lights_query: Extract<Query<(Entity, &SpotLight2d, &GlobalTransform)>>,
+    line_lights_query: Extract<Query<(Entity, &LineLight2d, &GlobalTransform)>>,
+    directional_lights_query: Extract<Query<(Entity, &DirectionalLight2d)>>,
 ) {
     let mut id = 0;
 
@@ -134,7 +315,20 @@ pub fn extract_lights(
         point_lights_query
             .iter()
             .map(|(entity, light, transform)| {
+                let rotation = transform
+                    .to_scale_rotation_translation()
+                    .1
+                    .to_euler(EulerRot::ZYX)
+                    .0;
                 let transform = GlobalTransform::from_translation(transform.translation());
+                let range = light.max_range();
+                let (range_scale, range_rotation) = match light.elliptical_range {
+                    Some(elliptical_range) if range > 0. => (
+                        (elliptical_range.range / range).max(Vec2::splat(f32::EPSILON)),
+                        rotation + elliptical_range.rotation,
+                    ),
+                    _ => (Vec2::ONE, 0.),
+                };
                 id += 1;
                 (
                     entity,
@@ -143,9 +337,16 @@ pub fn extract_lights(
                             id: id - 1,
                             color: light.color,
                             intensity: light.intensity,
-                            range: light.range,
+                            range,
                             radius: light.radius,
+                            height: light.height,
                             spot_light_angles: [0., std::f32::consts::TAU],
+                            spot_light_inner_extent: std::f32::consts::TAU,
+                            cookie: light.cookie.as_ref().map(|c| c.id()),
+                            rotation,
+                            falloff: light.falloff.clone(),
+                            range_scale,
+                            range_rotation,
                         },
                         transform,
                     ),
@@ -158,6 +359,11 @@ pub fn extract_lights(
         spot_lights_query
             .iter()
             .map(|(entity, light, transform)| {
+                let rotation = transform
+                    .to_scale_rotation_translation()
+                    .1
+                    .to_euler(EulerRot::ZYX)
+                    .0;
                 let transform = GlobalTransform::from_translation(transform.translation());
                 id += 1;
                 (
@@ -169,7 +375,20 @@ pub fn extract_lights(
                             intensity: light.intensity,
                             range: light.range,
                             radius: light.radius,
-                            spot_light_angles: light.sector.into_extent(),
+                            height: light.height,
+                            spot_light_angles: if light.absolute_sector {
+                                light.sector.into_extent()
+                            } else {
+                                light.sector.rotated(rotation).into_extent()
+                            },
+                            spot_light_inner_extent: light
+                                .inner_extent
+                                .unwrap_or(std::f32::consts::TAU),
+                            cookie: light.cookie.as_ref().map(|c| c.id()),
+                            rotation,
+                            falloff: light.falloff.clone(),
+                            range_scale: Vec2::ONE,
+                            range_rotation: 0.,
                         },
                         transform,
                     ),
@@ -177,16 +396,75 @@ pub fn extract_lights(
             })
             .collect::<Vec<_>>(),
     );
+
+    commands.insert_or_spawn_batch(
+        line_lights_query
+            .iter()
+            .map(|(entity, light, transform)| {
+                let half_segment = Vec3::new(light.length / 2., 0., 0.);
+                (
+                    entity,
+                    ExtractedLineLight2d {
+                        color: light.color,
+                        intensity: light.intensity,
+                        start: transform.transform_point(-half_segment),
+                        end: transform.transform_point(half_segment),
+                        thickness: light.thickness,
+                        range: light.range,
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    commands.insert_or_spawn_batch(
+        directional_lights_query
+            .iter()
+            .map(|(entity, light)| {
+                (
+                    entity,
+                    ExtractedDirectionalLight2d {
+                        color: light.color,
+                        intensity: light.intensity,
+                        direction: light.direction.normalize_or_zero(),
+                        shadow_length: light.shadow_length,
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[derive(SystemParam)]
+pub struct ExtractedLightsQuery<'w, 's> {
+    point_lights: Query<'w, 's, (&'static ExtractedPointLight2d, &'static GlobalTransform)>,
+    line_lights: Query<'w, 's, &'static ExtractedLineLight2d>,
+    directional_lights: Query<'w, 's, &'static ExtractedDirectionalLight2d>,
 }
 
 pub fn prepare_lights(
     mut commands: Commands,
-    main_views: Query<(Entity, &ExtractedView, &VisibleEntities), With<ViewTarget>>,
-    lights_query: Query<(&ExtractedPointLight2d, &GlobalTransform)>,
+    main_views: Query<
+        (
+            Entity,
+            &ExtractedView,
+            &VisibleEntities,
+            Option<&Lighting2dApproach>,
+        ),
+        With<ViewTarget>,
+    >,
+    lights_query: ExtractedLightsQuery,
+    cookie_storage: Res<LightCookieStorage>,
     ambient_light: Res<AmbientLight2d>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
 ) {
+    let ExtractedLightsQuery {
+        point_lights: lights_query,
+        line_lights: line_lights_query,
+        directional_lights: directional_lights_query,
+    } = lights_query;
+
     commands.insert_resource(GpuAmbientLight2dBuffer::new(
         GpuAmbientLight2d {
             color: ambient_light.color.rgba_linear_to_vec4(),
@@ -196,13 +474,10 @@ pub fn prepare_lights(
         &render_queue,
     ));
 
-    for (main_view_entity, main_view, visible_entities) in &main_views {
+    for (main_view_entity, main_view, visible_entities, approach) in &main_views {
         let mut buffer = GpuLights2d::new(&render_device);
 
-        let main_view_pos_ws = main_view.transform.translation();
-        let view_proj = main_view.view_projection.unwrap_or_else(|| {
-            main_view.projection * main_view.transform.compute_matrix().inverse()
-        });
+        let screen_space = LightScreenSpace::new(main_view);
 
         let mut visible_lights = visible_entities
             .entities
@@ -212,23 +487,65 @@ pub fn prepare_lights(
         radsort::sort_by_key(&mut visible_lights, |(light, _)| light.id);
 
         for (light, light_transform) in &visible_lights {
-            let position_ws = light_transform.translation().extend(1.);
-
-            let mut position_ndc = (view_proj * position_ws).xy();
-            position_ndc.y = -position_ndc.y;
-            let range_ndc =
-                view_proj * (Vec3::new(light.range, 0., 0.) + main_view_pos_ws).extend(1.);
-
-            let range_ndc = range_ndc.x / range_ndc.w / 2.;
+            let range_ndc = screen_space.length(light.range);
             let radius_ndc = light.radius / light.range * range_ndc;
+            let (falloff, falloff_curve) = match &light.falloff {
+                LightFalloff2d::Linear => (LIGHT_FALLOFF_LINEAR, &[][..]),
+                LightFalloff2d::Smooth => (LIGHT_FALLOFF_SMOOTH, &[][..]),
+                LightFalloff2d::InverseSquare => (LIGHT_FALLOFF_INVERSE_SQUARE, &[][..]),
+                LightFalloff2d::Custom(curve) => (LIGHT_FALLOFF_CUSTOM, &curve[..]),
+            };
+            let falloff_curve_offset = buffer.add_falloff_curve(falloff_curve);
 
             buffer.add_point_light(GpuPointLight2d {
                 intensity: light.intensity,
-                position_ss: (position_ndc + 1.) / 2.,
+                position_ss: screen_space.position(light_transform.translation()),
                 radius_ss: radius_ndc,
+                height_ss: screen_space.length(light.height),
                 range_ss: range_ndc,
                 color: light.color.rgba_linear_to_vec4(),
                 angles: light.spot_light_angles,
+                inner_extent: light.spot_light_inner_extent,
+                cookie_index: cookie_storage.get_index(light.cookie),
+                rotation: light.rotation,
+                falloff,
+                falloff_curve_offset,
+                falloff_curve_len: falloff_curve.len() as u32,
+                range_scale: light.range_scale,
+                range_rotation: light.range_rotation,
+            });
+        }
+
+        let line_lights = visible_entities
+            .entities
+            .iter()
+            .filter_map(|e| line_lights_query.get(*e).ok())
+            .collect::<Vec<_>>();
+        if !line_lights.is_empty() && !approach.copied().unwrap_or_default().renders_line_lights() {
+            warn_once!("LineLight2d is only rendered when using ray_marching, ignoring it.");
+        }
+
+        for light in line_lights {
+            buffer.add_line_light(GpuLineLight2d {
+                intensity: light.intensity,
+                start_ss: screen_space.position(light.start),
+                end_ss: screen_space.position(light.end),
+                thickness_ss: screen_space.length(light.thickness),
+                range_ss: screen_space.length(light.range),
+                color: light.color.rgba_linear_to_vec4(),
+            });
+        }
+
+        for light in visible_entities
+            .entities
+            .iter()
+            .filter_map(|e| directional_lights_query.get(*e).ok())
+        {
+            buffer.add_directional_light(GpuDirectionalLight2d {
+                color: light.color.rgba_linear_to_vec4(),
+                intensity: light.intensity,
+                direction_ss: screen_space.direction(light.direction),
+                shadow_length_ss: screen_space.length(light.shadow_length),
             });
         }
 
@@ -0,0 +1,211 @@
+use bevy::{
+    ecs::{
+        query::QueryState,
+        system::lifetimeless::Read,
+        world::{FromWorld, World},
+    },
+    math::UVec3,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_resource::{BindGroupEntries, ComputePassDescriptor, PipelineCache},
+        renderer::RenderContext,
+        view::ViewTarget,
+    },
+};
+
+use crate::render::{
+    ray_marching::{SdfTextureStorage, SHADOW_WORK_GROUP_SIZE},
+    surface::ViewSurfaces2d,
+    universal_buffers::NumberBuffer,
+    DynamicUniformIndex,
+};
+
+use super::{
+    pipeline::{RadianceCascadesCascadePassPipeline, RadianceCascadesGiPassPipeline},
+    GpuRadianceCascadesMetaBuffer, RadianceCascadesCount, RadianceCascadesMeta,
+    RadianceCascadesStorage,
+};
+
+#[derive(RenderLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum RadianceCascadesNode {
+    CascadePass,
+    GiPass,
+}
+
+type CascadePassView = (
+    Read<ViewTarget>,
+    Read<ViewSurfaces2d>,
+    Read<DynamicUniformIndex<RadianceCascadesMeta>>,
+    Read<RadianceCascadesCount>,
+);
+
+pub struct RadianceCascadesCascadePassNode {
+    main_view_query: QueryState<CascadePassView>,
+}
+
+impl FromWorld for RadianceCascadesCascadePassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for RadianceCascadesCascadePassNode {
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok((view_target, surfaces, meta_offset, cascade_count)) =
+            self.main_view_query.get_manual(world, graph.view_entity())
+        else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<RadianceCascadesCascadePassPipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.cached_id)
+        else {
+            return Ok(());
+        };
+
+        let main_view_entity = graph.view_entity();
+        let sdf_textures = world.resource::<SdfTextureStorage>();
+        let radiance_cascades = world.resource::<RadianceCascadesStorage>();
+        let meta_buffer = world.resource::<GpuRadianceCascadesMetaBuffer>();
+        let number_buffer = world.resource::<NumberBuffer>();
+
+        let textures = radiance_cascades.get_textures(main_view_entity);
+        let sdf_texture = sdf_textures.get_sdf_texture(main_view_entity);
+
+        let work_group_count = UVec3 {
+            x: textures.cascade_size().x.div_ceil(SHADOW_WORK_GROUP_SIZE.x),
+            y: textures.cascade_size().y.div_ceil(SHADOW_WORK_GROUP_SIZE.y),
+            z: SHADOW_WORK_GROUP_SIZE.z,
+        };
+
+        // From the last cascade to the first one, each merging the one above.
+        for cascade in (0..cascade_count.0).rev() {
+            let bind_group = render_context.render_device().create_bind_group(
+                "light_2d_cascade_pass_bind_group",
+                &pipeline.cascade_pass_layout,
+                &BindGroupEntries::sequential((
+                    &sdf_texture.get_texture().texture_view,
+                    view_target.main_texture_view(),
+                    &surfaces.emissive.texture.default_view,
+                    &textures.irradiance().default_view,
+                    &pipeline.screen_sampler,
+                    &textures.get_cascade(cascade + 1).default_view,
+                    &textures.get_cascade(cascade).default_view,
+                    meta_buffer.binding(),
+                    number_buffer.binding(),
+                )),
+            );
+
+            let mut compute_pass =
+                render_context
+                    .command_encoder()
+                    .begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("light_2d_cascade_pass"),
+                        timestamp_writes: None,
+                    });
+
+            compute_pass.set_pipeline(compute_pipeline);
+            compute_pass.set_bind_group(
+                0,
+                &bind_group,
+                &[meta_offset.index(), number_buffer.get_index(cascade)],
+            );
+            compute_pass.dispatch_workgroups(
+                work_group_count.x,
+                work_group_count.y,
+                work_group_count.z,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub struct RadianceCascadesGiPassNode {
+    main_view_query: QueryState<Read<DynamicUniformIndex<RadianceCascadesMeta>>>,
+}
+
+impl FromWorld for RadianceCascadesGiPassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for RadianceCascadesGiPassNode {
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok(meta_offset) = self.main_view_query.get_manual(world, graph.view_entity()) else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<RadianceCascadesGiPassPipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.cached_id)
+        else {
+            return Ok(());
+        };
+
+        let radiance_cascades = world.resource::<RadianceCascadesStorage>();
+        let meta_buffer = world.resource::<GpuRadianceCascadesMetaBuffer>();
+
+        let textures = radiance_cascades.get_textures(graph.view_entity());
+        let bind_group = render_context.render_device().create_bind_group(
+            "light_2d_gi_pass_bind_group",
+            &pipeline.gi_pass_layout,
+            &BindGroupEntries::sequential((
+                &textures.get_cascade(0).default_view,
+                &textures.global_illumination().default_view,
+                meta_buffer.binding(),
+            )),
+        );
+
+        let gi_size = textures.global_illumination().texture.size();
+        let work_group_count = UVec3 {
+            x: gi_size.width.div_ceil(SHADOW_WORK_GROUP_SIZE.x),
+            y: gi_size.height.div_ceil(SHADOW_WORK_GROUP_SIZE.y),
+            z: SHADOW_WORK_GROUP_SIZE.z,
+        };
+
+        let mut compute_pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("light_2d_gi_pass"),
+                    timestamp_writes: None,
+                });
+
+        compute_pass.set_pipeline(compute_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[meta_offset.index()]);
+        compute_pass.dispatch_workgroups(
+            work_group_count.x,
+            work_group_count.y,
+            work_group_count.z,
+        );
+
+        Ok(())
+    }
+}
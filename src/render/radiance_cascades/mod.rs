@@ -0,0 +1,402 @@
+use bevy::{
+    app::{App, Plugin},
+    asset::{load_internal_asset, Handle},
+    core_pipeline::core_2d::{graph::Core2d, Camera2d},
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource, SystemParam},
+    },
+    math::UVec2,
+    render::{
+        extract_resource::ExtractResourcePlugin,
+        render_graph::RenderGraphApp,
+        render_resource::{
+            BindingResource, DynamicUniformBuffer, Extent3d, Shader, ShaderType, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::CachedTexture,
+        view::ViewTarget,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    utils::hashbrown::hash_map::Entry,
+};
+
+use crate::ecs::{
+    radiance_cascades::{RadianceCascades2d, RadianceCascadesConfig},
+    Lighting2dApproach,
+};
+
+use self::{
+    graph::{RadianceCascadesCascadePassNode, RadianceCascadesGiPassNode, RadianceCascadesNode},
+    pipeline::{RadianceCascadesCascadePassPipeline, RadianceCascadesGiPassPipeline},
+};
+
+use super::{
+    ray_marching::{self, SdfTextureStorage},
+    DynamicUniformIndex,
+};
+
+pub mod graph;
+pub mod pipeline;
+
+pub const RADIANCE_CASCADES_PROBES: Handle<Shader> =
+    Handle::weak_from_u128(7413265489732154687946513215467);
+pub const RADIANCE_CASCADES_CASCADE_PASS_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(2136548746513265489713246587913);
+pub const RADIANCE_CASCADES_GI_PASS_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(9846513265487913265487132654987);
+
+pub const CASCADE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// Light received by every pixel, written by the main pass.
+pub const IRRADIANCE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// Enough to cover 5460 pixels with the default interval.
+pub const MAX_CASCADES: u32 = 6;
+
+/// Global illumination traced through the SDF built by `ray_marching`.
+///
+/// Rays stop at [`ShadowCaster2d`](crate::ecs::ShadowCaster2d)s, which emit and bounce light,
+/// and at emissive sprites, which only emit light.
+pub struct RadianceCascadesPlugin;
+
+impl Plugin for RadianceCascadesPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            RADIANCE_CASCADES_PROBES,
+            "shaders/probes.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            RADIANCE_CASCADES_CASCADE_PASS_SHADER,
+            "shaders/cascade_pass.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            RADIANCE_CASCADES_GI_PASS_SHADER,
+            "shaders/gi_pass.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(ExtractResourcePlugin::<RadianceCascadesConfig>::default())
+            .register_type::<RadianceCascadesConfig>()
+            .register_type::<RadianceCascades2d>()
+            .init_resource::<RadianceCascadesConfig>();
+
+        let render_app = app.sub_app_mut(RenderApp);
+
+        render_app
+            .init_resource::<GpuRadianceCascadesMetaBuffer>()
+            .init_resource::<RadianceCascadesStorage>()
+            .add_systems(ExtractSchedule, extract_radiance_cascades)
+            .add_systems(
+                Render,
+                prepare
+                    .after(ray_marching::prepare)
+                    .in_set(RenderSet::Prepare),
+            )
+            .add_render_graph_node::<RadianceCascadesCascadePassNode>(
+                Core2d,
+                RadianceCascadesNode::CascadePass,
+            )
+            .add_render_graph_node::<RadianceCascadesGiPassNode>(
+                Core2d,
+                RadianceCascadesNode::GiPass,
+            )
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    ray_marching::graph::Shadow2dNode::Shadow2dSdfPass,
+                    RadianceCascadesNode::CascadePass,
+                    RadianceCascadesNode::GiPass,
+                    ray_marching::graph::Shadow2dNode::Shadow2dMainPass,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+
+        render_app
+            .init_resource::<RadianceCascadesCascadePassPipeline>()
+            .init_resource::<RadianceCascadesGiPassPipeline>();
+    }
+}
+
+type RadianceCascadesCameraFilter = (With<Camera2d>, With<RadianceCascades2d>);
+
+pub fn extract_radiance_cascades(
+    mut commands: Commands,
+    cameras_query: Extract<Query<Entity, RadianceCascadesCameraFilter>>,
+) {
+    commands.insert_or_spawn_batch(
+        cameras_query
+            .iter()
+            .map(|entity| (entity, RadianceCascades2d))
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[derive(SystemParam)]
+pub struct RadianceCascadesResources<'w> {
+    storage: ResMut<'w, RadianceCascadesStorage>,
+    meta_buffer: ResMut<'w, GpuRadianceCascadesMetaBuffer>,
+    config: Res<'w, RadianceCascadesConfig>,
+}
+
+pub fn prepare(
+    mut commands: Commands,
+    main_view_query: Query<(Entity, &ViewTarget, &Lighting2dApproach), With<RadianceCascades2d>>,
+    sdf_texture_storage: Res<SdfTextureStorage>,
+    resources: RadianceCascadesResources,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let RadianceCascadesResources {
+        mut storage,
+        mut meta_buffer,
+        config,
+    } = resources;
+    meta_buffer.clear();
+    let interval = config.interval.max(1.);
+    let mut main_views = Vec::new();
+
+    for (main_view_entity, view_target, approach) in &main_view_query {
+        if *approach != Lighting2dApproach::RayMarching {
+            continue;
+        }
+        main_views.push(main_view_entity);
+
+        let sdf_size = sdf_texture_storage
+            .get_sdf_texture(main_view_entity)
+            .size()
+            .max(UVec2::ONE);
+        // The irradiance is written next to the view target by the main pass,
+        // so it covers the whole target rather than the viewport.
+        let target_size = view_target.main_texture().size();
+        let screen_size = UVec2::new(target_size.width, target_size.height);
+
+        // The cascades end at interval * (4^n - 1) / 3, which should reach across the screen.
+        let diagonal = sdf_size.as_vec2().length();
+        let cascade_count = (1..MAX_CASCADES)
+            .find(|n| interval * (4f32.powi(*n as i32) - 1.) / 3. >= diagonal)
+            .unwrap_or(MAX_CASCADES);
+
+        let textures =
+            storage.try_add_main_view(main_view_entity, sdf_size, screen_size, &render_device);
+
+        let offset = meta_buffer.add_meta(RadianceCascadesMeta {
+            size: sdf_size,
+            cascade_size: textures.cascade_size,
+            cascade_count,
+            interval,
+            intensity: config.intensity,
+            bounce: config.bounce,
+        });
+
+        commands
+            .entity(main_view_entity)
+            .insert((offset, RadianceCascadesCount(cascade_count)));
+    }
+
+    storage.retain_main_views(&main_views);
+    meta_buffer.write_buffer(&render_device, &render_queue);
+}
+
+/// How many cascades are traced for the view.
+#[derive(Component)]
+pub struct RadianceCascadesCount(pub u32);
+
+pub struct RadianceCascadesTextures {
+    /// Ping-ponged from the last cascade to the first one.
+    cascades: [CachedTexture; 2],
+    global_illumination: CachedTexture,
+    irradiance: CachedTexture,
+    sdf_size: UVec2,
+    screen_size: UVec2,
+    cascade_size: UVec2,
+}
+
+impl RadianceCascadesTextures {
+    pub fn new(sdf_size: UVec2, screen_size: UVec2, render_device: &RenderDevice) -> Self {
+        // Probes of every cascade fill whole blocks.
+        let block = 2 << (MAX_CASCADES - 1);
+        let cascade_size = (sdf_size + block - 1) / block * block;
+        let storage_usage = TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+
+        Self {
+            cascades: [
+                Self::create_texture(
+                    "radiance_cascade_primary",
+                    cascade_size,
+                    CASCADE_FORMAT,
+                    storage_usage,
+                    render_device,
+                ),
+                Self::create_texture(
+                    "radiance_cascade_secondary",
+                    cascade_size,
+                    CASCADE_FORMAT,
+                    storage_usage,
+                    render_device,
+                ),
+            ],
+            global_illumination: Self::create_texture(
+                "global_illumination_2d",
+                sdf_size,
+                CASCADE_FORMAT,
+                storage_usage,
+                render_device,
+            ),
+            // Kept between frames, as the cascades bounce the light of the previous frame.
+            irradiance: Self::create_texture(
+                "irradiance_2d",
+                screen_size,
+                IRRADIANCE_FORMAT,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                render_device,
+            ),
+            sdf_size,
+            screen_size,
+            cascade_size,
+        }
+    }
+
+    /// The texture cascade `index` is written into.
+    #[inline]
+    pub fn get_cascade(&self, index: u32) -> &CachedTexture {
+        &self.cascades[(index % 2) as usize]
+    }
+
+    #[inline]
+    pub fn global_illumination(&self) -> &CachedTexture {
+        &self.global_illumination
+    }
+
+    #[inline]
+    pub fn irradiance(&self) -> &CachedTexture {
+        &self.irradiance
+    }
+
+    #[inline]
+    pub fn cascade_size(&self) -> UVec2 {
+        self.cascade_size
+    }
+
+    fn create_texture(
+        label: &'static str,
+        size: UVec2,
+        format: TextureFormat,
+        usage: TextureUsages,
+        render_device: &RenderDevice,
+    ) -> CachedTexture {
+        let texture = render_device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        CachedTexture {
+            default_view: texture.create_view(&TextureViewDescriptor::default()),
+            texture,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RadianceCascadesStorage(EntityHashMap<RadianceCascadesTextures>);
+
+impl RadianceCascadesStorage {
+    pub fn try_add_main_view(
+        &mut self,
+        main_view: Entity,
+        sdf_size: UVec2,
+        screen_size: UVec2,
+        render_device: &RenderDevice,
+    ) -> &RadianceCascadesTextures {
+        match self.0.entry(main_view) {
+            Entry::Occupied(occ_e)
+                if occ_e.get().sdf_size == sdf_size && occ_e.get().screen_size == screen_size =>
+            {
+                occ_e.into_mut()
+            }
+            entry => entry
+                .insert(RadianceCascadesTextures::new(
+                    sdf_size,
+                    screen_size,
+                    render_device,
+                ))
+                .into_mut(),
+        }
+    }
+
+    /// Drops the textures of views that no longer use radiance cascades.
+    pub fn retain_main_views(&mut self, main_views: &[Entity]) {
+        self.0.retain(|main_view, _| main_views.contains(main_view));
+    }
+
+    #[inline]
+    pub fn get_textures(&self, main_view: Entity) -> &RadianceCascadesTextures {
+        self.0.get(&main_view).unwrap()
+    }
+
+    /// `None` for views without radiance cascades.
+    #[inline]
+    pub fn try_get_textures(&self, main_view: Entity) -> Option<&RadianceCascadesTextures> {
+        self.0.get(&main_view)
+    }
+}
+
+#[derive(ShaderType)]
+pub struct RadianceCascadesMeta {
+    pub size: UVec2,
+    pub cascade_size: UVec2,
+    pub cascade_count: u32,
+    pub interval: f32,
+    pub intensity: f32,
+    pub bounce: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct GpuRadianceCascadesMetaBuffer(DynamicUniformBuffer<RadianceCascadesMeta>);
+
+impl GpuRadianceCascadesMetaBuffer {
+    #[inline]
+    pub fn add_meta(
+        &mut self,
+        meta: RadianceCascadesMeta,
+    ) -> DynamicUniformIndex<RadianceCascadesMeta> {
+        DynamicUniformIndex::new(self.0.push(&meta))
+    }
+
+    #[inline]
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.0.binding().unwrap()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    #[inline]
+    pub fn write_buffer(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        self.0.write_buffer(render_device, render_queue);
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::{
+    ecs::{
+        system::Resource,
+        world::{FromWorld, World},
+    },
+    render::{
+        render_resource::{
+            AddressMode, BindGroupLayout, BindGroupLayoutEntries, CachedComputePipelineId,
+            ComputePipelineDescriptor, FilterMode, PipelineCache, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, StorageTextureAccess, TextureFormat,
+            TextureSampleType,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+use bevy::render::render_resource::binding_types as binding;
+
+use super::{
+    RadianceCascadesMeta, CASCADE_FORMAT, RADIANCE_CASCADES_CASCADE_PASS_SHADER,
+    RADIANCE_CASCADES_GI_PASS_SHADER,
+};
+
+#[derive(Resource)]
+pub struct RadianceCascadesCascadePassPipeline {
+    pub cached_id: CachedComputePipelineId,
+    pub cascade_pass_layout: BindGroupLayout,
+    pub screen_sampler: Sampler,
+}
+
+impl FromWorld for RadianceCascadesCascadePassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let cascade_pass_layout = render_device.create_bind_group_layout(
+            "light_2d_cascade_pass_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    // Sdf texture
+                    binding::texture_storage_2d(
+                        TextureFormat::Rgba32Float,
+                        StorageTextureAccess::ReadOnly,
+                    ),
+                    // Main texture
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface emissive
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Irradiance
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                    // Upper cascade
+                    binding::texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Destination cascade
+                    binding::texture_storage_2d(CASCADE_FORMAT, StorageTextureAccess::WriteOnly),
+                    // Radiance cascades meta
+                    binding::uniform_buffer::<RadianceCascadesMeta>(true),
+                    // Cascade index
+                    binding::uniform_buffer::<u32>(true),
+                ),
+            ),
+        );
+
+        let screen_sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("light_2d_cascade_pass_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let cached_id =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("light_2d_cascade_pass_pipeline".into()),
+                    layout: vec![cascade_pass_layout.clone()],
+                    push_constant_ranges: vec![],
+                    shader: RADIANCE_CASCADES_CASCADE_PASS_SHADER,
+                    shader_defs: vec![],
+                    entry_point: "main".into(),
+                });
+
+        Self {
+            cached_id,
+            cascade_pass_layout,
+            screen_sampler,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct RadianceCascadesGiPassPipeline {
+    pub cached_id: CachedComputePipelineId,
+    pub gi_pass_layout: BindGroupLayout,
+}
+
+impl FromWorld for RadianceCascadesGiPassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let gi_pass_layout = render_device.create_bind_group_layout(
+            "light_2d_gi_pass_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    // First cascade
+                    binding::texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Global illumination
+                    binding::texture_storage_2d(CASCADE_FORMAT, StorageTextureAccess::WriteOnly),
+                    // Radiance cascades meta
+                    binding::uniform_buffer::<RadianceCascadesMeta>(true),
+                ),
+            ),
+        );
+
+        let cached_id =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("light_2d_gi_pass_pipeline".into()),
+                    layout: vec![gi_pass_layout.clone()],
+                    push_constant_ranges: vec![],
+                    shader: RADIANCE_CASCADES_GI_PASS_SHADER,
+                    shader_defs: vec![],
+                    entry_point: "main".into(),
+                });
+
+        Self {
+            cached_id,
+            gi_pass_layout,
+        }
+    }
+}
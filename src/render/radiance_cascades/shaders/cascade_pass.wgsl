@@ -0,0 +1,102 @@
+#import bevy_incandescent::radiance_cascades::probes::{
+    get_probe_spacing, merge_probes, RadianceCascadesMeta
+}
+
+@group(0) @binding(0)
+var sdf_tex: texture_storage_2d<rgba32float, read>;
+
+@group(0) @binding(1)
+var main_tex: texture_2d<f32>;
+
+@group(0) @binding(2)
+var surface_emissive: texture_2d<f32>;
+
+@group(0) @binding(3)
+var irradiance_tex: texture_2d<f32>;
+
+@group(0) @binding(4)
+var screen_sampler: sampler;
+
+@group(0) @binding(5)
+var upper_cascade: texture_2d<f32>;
+
+@group(0) @binding(6)
+var dest_cascade: texture_storage_2d<rgba16float, write>;
+
+@group(0) @binding(7)
+var<uniform> rc_meta: RadianceCascadesMeta;
+
+@group(0) @binding(8)
+var<uniform> cascade: u32;
+
+const TAU: f32 = 6.28318530718;
+const MAX_STEPS: u32 = 32u;
+
+// The light leaving the caster at px towards the ray: its emission, and the light
+// received last frame in front of it, tinted by its color.
+fn get_hit_radiance(px: vec2f, dir: vec2f) -> vec3f {
+    let size = vec2f(rc_meta.size);
+    let uv = px / size;
+    let emissive = textureSampleLevel(surface_emissive, screen_sampler, uv, 0.).rgb;
+    let albedo = textureSampleLevel(main_tex, screen_sampler, uv, 0.).rgb;
+    let irradiance = textureSampleLevel(irradiance_tex, screen_sampler, (px - dir * 1.5) / size, 0.).rgb;
+    return emissive + albedo * irradiance * rc_meta.bounce;
+}
+
+// Returns the radiance in rgb, and in a whether the ray passed through the whole interval.
+fn trace_interval(origin: vec2f, dir: vec2f, start: f32, end: f32) -> vec4f {
+    let size = vec2f(rc_meta.size);
+    // Emissive sprites aren't in the sdf, so steps are capped to not skip over them.
+    let max_step = max((end - start) / f32(MAX_STEPS), 0.5);
+    var t = start;
+
+    for (var i = 0u; i < MAX_STEPS; i++) {
+        let px = origin + dir * t;
+        if any(px < vec2f(0.)) || any(px >= size) {
+            break;
+        }
+
+        let closest = textureLoad(sdf_tex, vec2i(px)).r;
+        if closest < 0.5 {
+            return vec4f(get_hit_radiance(px, dir), 0.);
+        }
+
+        // The ray stops at the first emissive sprite, whether it casts shadows or not.
+        let emissive = textureSampleLevel(surface_emissive, screen_sampler, px / size, 0.).rgb;
+        if any(emissive > vec3f(0.)) {
+            return vec4f(emissive, 0.);
+        }
+
+        t += min(closest, max_step);
+        if t >= end {
+            break;
+        }
+    }
+    return vec4f(0., 0., 0., 1.);
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
+    let texel = invocation_id.xy;
+    if any(texel >= rc_meta.cascade_size) {
+        return;
+    }
+
+    let spacing = get_probe_spacing(cascade);
+    let local = texel % spacing;
+    let dir_index = local.y * spacing + local.x;
+    let angle = (f32(dir_index) + 0.5) / f32(spacing * spacing) * TAU;
+    let dir = vec2f(cos(angle), sin(angle));
+    let probe_px = (vec2f(texel / spacing) + 0.5) * f32(spacing);
+
+    // Intervals start where the previous cascade ended, and are 4 times as long.
+    let start = rc_meta.interval * (f32(1u << (2u * cascade)) - 1.) / 3.;
+    let end = start * 4. + rc_meta.interval;
+
+    var radiance = trace_interval(probe_px, dir, start, end);
+    if radiance.a > 0. && cascade + 1u < rc_meta.cascade_count {
+        let upper = merge_probes(upper_cascade, rc_meta.cascade_size, cascade + 1u, probe_px, dir_index * 4u);
+        radiance = vec4f(radiance.rgb + upper.rgb * radiance.a, upper.a * radiance.a);
+    }
+    textureStore(dest_cascade, texel, radiance);
+}
@@ -0,0 +1,22 @@
+#import bevy_incandescent::radiance_cascades::probes::{merge_probes, RadianceCascadesMeta}
+
+@group(0) @binding(0)
+var cascade_tex: texture_2d<f32>;
+
+@group(0) @binding(1)
+var global_illumination: texture_storage_2d<rgba16float, write>;
+
+@group(0) @binding(2)
+var<uniform> rc_meta: RadianceCascadesMeta;
+
+@compute @workgroup_size(16, 16, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
+    let px = invocation_id.xy;
+    if any(px >= rc_meta.size) {
+        return;
+    }
+
+    // The first cascade has exactly 4 directions per probe.
+    let radiance = merge_probes(cascade_tex, rc_meta.cascade_size, 0u, vec2f(px) + 0.5, 0u);
+    textureStore(global_illumination, px, vec4f(radiance.rgb * rc_meta.intensity, 1.));
+}
@@ -0,0 +1,47 @@
+#define_import_path bevy_incandescent::radiance_cascades::probes
+
+struct RadianceCascadesMeta {
+    // Size of the sdf texture.
+    size: vec2u,
+    cascade_size: vec2u,
+    cascade_count: u32,
+    interval: f32,
+    intensity: f32,
+    bounce: f32,
+}
+
+// Probes of cascade n are placed every 2^(n+1) pixels, and store the radiance of their
+// 4^(n+1) directions in a block of 2^(n+1)x2^(n+1) texels.
+fn get_probe_spacing(cascade: u32) -> u32 {
+    return 2u << cascade;
+}
+
+// Bilinearly interpolates the 4 probes of the cascade around px, averaging
+// the 4 directions starting from first_dir of each probe.
+fn merge_probes(
+    cascade_tex: texture_2d<f32>,
+    cascade_size: vec2u,
+    cascade: u32,
+    px: vec2f,
+    first_dir: u32,
+) -> vec4f {
+    let spacing = get_probe_spacing(cascade);
+    let probes = vec2i(cascade_size / spacing);
+    let grid = px / f32(spacing) - 0.5;
+    let base = vec2i(floor(grid));
+    let t = fract(grid);
+
+    var radiance = vec4f(0.);
+    for (var y = 0; y < 2; y++) {
+        for (var x = 0; x < 2; x++) {
+            let probe = clamp(base + vec2i(x, y), vec2i(0), probes - 1);
+            let weight = mix(1. - t.x, t.x, f32(x)) * mix(1. - t.y, t.y, f32(y));
+            for (var i = 0u; i < 4u; i++) {
+                let dir = first_dir + i;
+                let texel = probe * i32(spacing) + vec2i(vec2u(dir % spacing, dir / spacing));
+                radiance += textureLoad(cascade_tex, texel, 0) * weight * 0.25;
+            }
+        }
+    }
+    return radiance;
+}
@@ -21,12 +21,18 @@ use bevy::{
 use crate::{
     ecs::ShadowView2d,
     render::{
+        caster::{render_casters, render_translucent_casters, CasterView2d, TransmittanceView2d},
+        cookie::LightCookieStorage,
         light::{GpuAmbientLight2dBuffer, GpuLights2d},
+        surface::ViewSurfaces2d,
         universal_buffers::NumberBuffer,
         DynamicUniformIndex,
     },
 };
 
+#[cfg(feature = "radiance_cascades")]
+use crate::render::radiance_cascades::RadianceCascadesStorage;
+
 use super::{
     pipeline::{
         Shadow2dJfaPassPipeline, Shadow2dJfaPrepassPipeline, Shadow2dMainPassPipeline,
@@ -45,13 +51,19 @@ pub enum Shadow2dNode {
 }
 
 pub struct Shadow2dMeshPassNode {
-    main_view_query: QueryState<(Read<RenderPhase<Transparent2d>>, Read<ShadowView2d>)>,
+    main_view_query: QueryState<(
+        Read<ShadowView2d>,
+        Read<TransmittanceView2d>,
+        Read<CasterView2d>,
+    )>,
+    caster_view_query: QueryState<(Read<RenderPhase<Transparent2d>>, Read<ViewUniformOffset>)>,
 }
 
 impl FromWorld for Shadow2dMeshPassNode {
     fn from_world(world: &mut World) -> Self {
         Self {
             main_view_query: world.query_filtered(),
+            caster_view_query: world.query_filtered(),
         }
     }
 }
@@ -59,6 +71,7 @@ impl FromWorld for Shadow2dMeshPassNode {
 impl Node for Shadow2dMeshPassNode {
     fn update(&mut self, world: &mut World) {
         self.main_view_query.update_archetypes(world);
+        self.caster_view_query.update_archetypes(world);
     }
 
     fn run<'w>(
@@ -67,32 +80,41 @@ impl Node for Shadow2dMeshPassNode {
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        let Ok((render_phase, shadow_view)) =
+        let Ok((shadow_view, transmittance_view, caster_view)) =
             self.main_view_query.get_manual(world, graph.view_entity())
         else {
             return Ok(());
         };
+        let Ok((render_phase, view_offset)) =
+            self.caster_view_query.get_manual(world, caster_view.0)
+        else {
+            return Ok(());
+        };
 
         let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
             label: Some("light_2d_mesh_pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view: &shadow_view.attachment.texture.default_view,
-                resolve_target: None,
-                ops: Operations::default(),
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
+            color_attachments: &[Some(shadow_view.attachment.get_attachment())],
+            ..Default::default()
         });
 
-        render_phase.render(&mut render_pass, world, graph.view_entity());
+        render_phase.render(&mut render_pass, world, caster_view.0);
+        render_casters(&mut render_pass, world, view_offset, false);
+        drop(render_pass);
+
+        render_translucent_casters(render_context, world, transmittance_view, view_offset);
 
         Ok(())
     }
 }
 
+type JfaPrepassView = (
+    Read<ShadowView2d>,
+    Read<TransmittanceView2d>,
+    Read<DynamicUniformIndex<SdfMeta>>,
+);
+
 pub struct Shadow2dJfaPrepassNode {
-    main_view_query: QueryState<(Read<ShadowView2d>, Read<DynamicUniformIndex<SdfMeta>>)>,
+    main_view_query: QueryState<JfaPrepassView>,
 }
 
 impl FromWorld for Shadow2dJfaPrepassNode {
@@ -114,7 +136,7 @@ impl Node for Shadow2dJfaPrepassNode {
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        let Ok((shadow_view, meta_offset)) =
+        let Ok((shadow_view, transmittance_view, meta_offset)) =
             self.main_view_query.get_manual(world, graph.view_entity())
         else {
             return Ok(());
@@ -140,6 +162,7 @@ impl Node for Shadow2dJfaPrepassNode {
                 main_texture_view,
                 &sdf_texture.get_primary_texture().texture_view,
                 gpu_meta_buffers.sdf_meta_binding(),
+                &transmittance_view.attachment.texture.default_view,
             )),
         );
 
@@ -350,6 +373,8 @@ pub struct Shadow2dMainPassNode {
         Read<ViewUniformOffset>,
         Read<DynamicUniformIndex<SdfMeta>>,
         Read<GpuLights2d>,
+        Read<ViewSurfaces2d>,
+        Read<TransmittanceView2d>,
     )>,
 }
 
@@ -372,16 +397,36 @@ impl Node for Shadow2dMainPassNode {
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        let Ok((view_target, view_offset, meta_offset, gpu_lights)) =
+        let Ok((view_target, view_offset, meta_offset, gpu_lights, surfaces, transmittance_view)) =
             self.main_view_query.get_manual(world, graph.view_entity())
         else {
             return Ok(());
         };
 
+        // Only present for views with radiance cascades.
+        #[cfg(feature = "radiance_cascades")]
+        let radiance_cascades = world
+            .resource::<RadianceCascadesStorage>()
+            .try_get_textures(graph.view_entity());
+
         let pipeline = world.resource::<Shadow2dMainPassPipeline>();
+        #[allow(unused_mut)]
+        let mut pipeline_id = if view_target.is_hdr() {
+            pipeline.hdr_cached_id
+        } else {
+            pipeline.cached_id
+        };
+        #[cfg(feature = "radiance_cascades")]
+        if radiance_cascades.is_some() {
+            pipeline_id = if view_target.is_hdr() {
+                pipeline.hdr_radiance_cascades_cached_id
+            } else {
+                pipeline.radiance_cascades_cached_id
+            };
+        }
         let Some(render_pipeline) = world
             .resource::<PipelineCache>()
-            .get_render_pipeline(pipeline.cached_id)
+            .get_render_pipeline(pipeline_id)
         else {
             return Ok(());
         };
@@ -390,6 +435,7 @@ impl Node for Shadow2dMainPassNode {
         let gpu_meta_buffers = world.resource::<GpuMetaBuffers>();
         let gpu_ambient_light_buffer = world.resource::<GpuAmbientLight2dBuffer>();
         let view_uniforms = world.resource::<ViewUniforms>();
+        let light_cookie_storage = world.resource::<LightCookieStorage>();
         let post_process = view_target.post_process_write();
 
         let sdf_texture = sdf_textures.get_sdf_texture(graph.view_entity());
@@ -404,16 +450,42 @@ impl Node for Shadow2dMainPassNode {
                 gpu_meta_buffers.sdf_meta_binding(),
                 gpu_ambient_light_buffer.binding(),
                 gpu_lights.point_lights_binding(),
+                gpu_lights.directional_lights_binding(),
+                gpu_lights.line_lights_binding(),
+                light_cookie_storage.texture_view(),
+                light_cookie_storage.sampler(),
+                gpu_lights.falloff_curves_binding(),
+                &surfaces.normal.texture.default_view,
+                &surfaces.material.texture.default_view,
+                &surfaces.emissive.texture.default_view,
+                &transmittance_view.attachment.texture.default_view,
             )),
         );
 
-        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-            label: Some("light_2d_main_pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view: &post_process.destination,
+        #[allow(unused_mut)]
+        let mut color_attachments = vec![Some(RenderPassColorAttachment {
+            view: &post_process.destination,
+            resolve_target: None,
+            ops: Operations::default(),
+        })];
+        #[cfg(feature = "radiance_cascades")]
+        let global_illumination_bind_group = radiance_cascades.map(|textures| {
+            color_attachments.push(Some(RenderPassColorAttachment {
+                view: &textures.irradiance().default_view,
                 resolve_target: None,
                 ops: Operations::default(),
-            })],
+            }));
+
+            render_context.render_device().create_bind_group(
+                "light_2d_main_pass_global_illumination_bind_group",
+                &pipeline.global_illumination_layout,
+                &BindGroupEntries::single(&textures.global_illumination().default_view),
+            )
+        });
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("light_2d_main_pass"),
+            color_attachments: &color_attachments,
             depth_stencil_attachment: None,
             timestamp_writes: None,
             occlusion_query_set: None,
@@ -421,6 +493,10 @@ impl Node for Shadow2dMainPassNode {
 
         render_pass.set_render_pipeline(render_pipeline);
         render_pass.set_bind_group(0, &bind_group, &[view_offset.offset, meta_offset.index()]);
+        #[cfg(feature = "radiance_cascades")]
+        if let Some(bind_group) = &global_illumination_bind_group {
+            render_pass.set_bind_group(1, bind_group, &[]);
+        }
         render_pass.draw(0..3, 0..1);
 
         Ok(())
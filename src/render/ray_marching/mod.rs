@@ -6,10 +6,11 @@ use bevy::{
         entity::{Entity, EntityHashMap},
         query::With,
         schedule::IntoSystemConfigs,
-        system::{Commands, Query, Res, ResMut, Resource},
+        system::{Commands, Query, Res, ResMut, Resource, SystemParam},
     },
-    math::{UVec2, UVec3, Vec2},
+    math::{UVec2, UVec3},
     render::{
+        color::Color,
         extract_resource::ExtractResourcePlugin,
         render_graph::RenderGraphApp,
         render_resource::{
@@ -27,7 +28,7 @@ use bevy::{
 };
 
 use crate::{
-    ecs::{ray_marching::RayMarchingConfig, ShadowView2d},
+    ecs::{ray_marching::RayMarchingConfig, Lighting2dApproach, ShadowView2d},
     render::ray_marching::{
         graph::{
             Shadow2dJfaPassNode, Shadow2dJfaPrepassNode, Shadow2dMainPassNode,
@@ -41,7 +42,7 @@ use self::pipeline::{
     Shadow2dJfaPrepassPipeline, Shadow2dMainPassPipeline, Shadow2dSdfPassPipeline,
 };
 
-use super::DynamicUniformIndex;
+use super::{caster::TransmittanceView2d, DynamicUniformIndex};
 
 pub mod graph;
 pub mod pipeline;
@@ -130,31 +131,43 @@ impl Plugin for RayMarchingApproachPlugin {
     }
 }
 
+#[derive(SystemParam)]
+pub struct SdfResources<'w> {
+    sdf_texture_storage: ResMut<'w, SdfTextureStorage>,
+    gpu_meta_buffers: ResMut<'w, GpuMetaBuffers>,
+    ray_marching_config: Res<'w, RayMarchingConfig>,
+}
+
 pub fn prepare(
     mut commands: Commands,
-    main_view_query: Query<(Entity, &ExtractedView), With<ViewTarget>>,
-    mut sdf_texture_storage: ResMut<SdfTextureStorage>,
+    main_view_query: Query<(Entity, &ExtractedView, &Lighting2dApproach), With<ViewTarget>>,
+    resources: SdfResources,
     mut texture_cache: ResMut<TextureCache>,
-    ray_marching_config: Res<RayMarchingConfig>,
-    mut gpu_meta_buffers: ResMut<GpuMetaBuffers>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
 ) {
+    let SdfResources {
+        mut sdf_texture_storage,
+        mut gpu_meta_buffers,
+        ray_marching_config,
+    } = resources;
     gpu_meta_buffers.clear();
 
-    for (main_view_entity, extracted_view) in &main_view_query {
-        let sdf_tex_size =
-            (2. / Vec2::new(
-                extracted_view.projection.x_axis[0],
-                extracted_view.projection.y_axis[1],
-            ) * ray_marching_config.scale)
-                .as_uvec2();
+    for (main_view_entity, extracted_view, approach) in &main_view_query {
+        if *approach != Lighting2dApproach::RayMarching {
+            continue;
+        }
+
+        let sdf_tex_size = get_sdf_size(extracted_view, ray_marching_config.scale);
         sdf_texture_storage.try_add_main_view(main_view_entity, sdf_tex_size, &render_device);
 
         let offset = gpu_meta_buffers.add_sdf_meta(SdfMeta {
             size: sdf_tex_size,
             alpha_threshold: ray_marching_config.alpha_threshold,
             edge_lighting: ray_marching_config.edge_lighting,
+            line_light_samples: ray_marching_config.line_light_samples,
+            softness: ray_marching_config.softness,
+            translucent_step: ray_marching_config.translucent_step.max(1.),
         });
 
         let main_view_texture = texture_cache.get(
@@ -178,14 +191,23 @@ pub fn prepare(
         commands.entity(main_view_entity).insert((
             offset,
             ShadowView2d {
-                attachment: ColorAttachment::new(main_view_texture, None, None),
+                attachment: ColorAttachment::new(main_view_texture, None, Some(Color::NONE)),
             },
+            TransmittanceView2d::new(sdf_tex_size, &mut texture_cache, &render_device),
         ));
     }
 
     gpu_meta_buffers.write_buffers(&render_device, &render_queue);
 }
 
+/// The SDF follows the viewport in physical pixels rather than the size of the view
+/// in world units, so its resolution doesn't change when the camera zooms.
+pub fn get_sdf_size(view: &ExtractedView, scale: f32) -> UVec2 {
+    (UVec2::new(view.viewport.z, view.viewport.w).as_vec2() * scale)
+        .as_uvec2()
+        .max(UVec2::ONE)
+}
+
 pub struct SdfTexture {
     primary: GpuImage,
     secondary: GpuImage,
@@ -209,7 +231,7 @@ impl SdfTexture {
     }
 
     pub fn get_texture(&self) -> &GpuImage {
-        if self.jfa_iterations % 2 == 0 {
+        if self.jfa_iterations.is_multiple_of(2) {
             self.get_primary_texture()
         } else {
             self.get_secondary_texture()
@@ -313,6 +335,9 @@ pub struct SdfMeta {
     pub size: UVec2,
     pub alpha_threshold: f32,
     pub edge_lighting: f32,
+    pub line_light_samples: u32,
+    pub softness: f32,
+    pub translucent_step: f32,
 }
 
 #[derive(Resource, Default)]
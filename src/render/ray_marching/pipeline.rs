@@ -14,13 +14,19 @@ use bevy::{
         },
         renderer::RenderDevice,
         texture::BevyDefault,
-        view::ViewUniform,
+        view::{ViewTarget, ViewUniform},
     },
 };
 
 use bevy::render::render_resource::binding_types as binding;
 
-use crate::render::light::{GpuAmbientLight2d, GpuPointLight2d};
+use crate::render::light::{
+    falloff_curves_shader_def, GpuAmbientLight2d, GpuDirectionalLight2d, GpuLineLight2d,
+    GpuPointLight2d,
+};
+
+#[cfg(feature = "radiance_cascades")]
+use crate::render::radiance_cascades::IRRADIANCE_FORMAT;
 
 use super::{
     SdfMeta, SHADOW_JFA_PASS_SHADER, SHADOW_JFA_PREPASS_SHADER, SHADOW_MAIN_PASS_SHADER,
@@ -51,6 +57,8 @@ impl FromWorld for Shadow2dJfaPrepassPipeline {
                     ),
                     // Sdf meta
                     binding::uniform_buffer::<SdfMeta>(true),
+                    // Transmittance of translucent casters
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
                 ),
             ),
         );
@@ -173,7 +181,15 @@ impl FromWorld for Shadow2dSdfPassPipeline {
 #[derive(Resource)]
 pub struct Shadow2dMainPassPipeline {
     pub cached_id: CachedRenderPipelineId,
+    pub hdr_cached_id: CachedRenderPipelineId,
+    /// Also adding the global illumination of views using radiance cascades.
+    #[cfg(feature = "radiance_cascades")]
+    pub radiance_cascades_cached_id: CachedRenderPipelineId,
+    #[cfg(feature = "radiance_cascades")]
+    pub hdr_radiance_cascades_cached_id: CachedRenderPipelineId,
     pub main_pass_layout: BindGroupLayout,
+    #[cfg(feature = "radiance_cascades")]
+    pub global_illumination_layout: BindGroupLayout,
     pub main_texture_sampler: Sampler,
 }
 
@@ -202,41 +218,105 @@ impl FromWorld for Shadow2dMainPassPipeline {
                     binding::uniform_buffer::<GpuAmbientLight2d>(false),
                     // Point lights
                     binding::storage_buffer_read_only::<GpuPointLight2d>(false),
+                    // Directional lights
+                    binding::storage_buffer_read_only::<GpuDirectionalLight2d>(false),
+                    // Line lights
+                    binding::storage_buffer_read_only::<GpuLineLight2d>(false),
+                    // Light cookies
+                    binding::texture_2d_array(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                    // Falloff curves, see `falloff_curves_shader_def`
+                    binding::storage_buffer_read_only::<f32>(false),
+                    // Surface normals
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface material
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Surface emissive
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Transmittance of translucent casters
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
                 ),
             ),
         );
 
+        #[cfg(feature = "radiance_cascades")]
+        let global_illumination_layout = render_device.create_bind_group_layout(
+            "light_2d_main_pass_global_illumination_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::FRAGMENT,
+                binding::texture_2d(TextureSampleType::Float { filterable: true }),
+            ),
+        );
+
         let main_texture_sampler = render_device.create_sampler(&SamplerDescriptor {
             label: Some("light_2d_main_pass_sampler".into()),
             ..Default::default()
         });
 
-        let cached_id =
-            world
-                .resource::<PipelineCache>()
-                .queue_render_pipeline(RenderPipelineDescriptor {
-                    label: Some("light_2d_main_pass_pipeline".into()),
-                    layout: vec![main_pass_layout.clone()],
-                    push_constant_ranges: vec![],
-                    vertex: fullscreen_shader_vertex_state(),
-                    primitive: PrimitiveState::default(),
-                    depth_stencil: None,
-                    multisample: MultisampleState::default(),
-                    fragment: Some(FragmentState {
-                        shader: SHADOW_MAIN_PASS_SHADER,
-                        shader_defs: vec![],
-                        entry_point: "fragment".into(),
-                        targets: vec![Some(ColorTargetState {
-                            format: TextureFormat::bevy_default(),
-                            blend: None,
-                            write_mask: ColorWrites::ALL,
-                        })],
-                    }),
-                });
+        // Writes into the view target, which is `Rgba16Float` for hdr cameras.
+        let descriptor = |label: &'static str, format: TextureFormat| RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: vec![main_pass_layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: fullscreen_shader_vertex_state(),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader: SHADOW_MAIN_PASS_SHADER,
+                shader_defs: vec![falloff_curves_shader_def(11)],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+        };
+
+        // The light received by each pixel is kept for the bounces of the next frame.
+        #[cfg(feature = "radiance_cascades")]
+        let radiance_cascades_descriptor = |label: &'static str, format: TextureFormat| {
+            let mut descriptor = descriptor(label, format);
+            descriptor.layout.push(global_illumination_layout.clone());
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.push("RADIANCE_CASCADES".into());
+            fragment.targets.push(Some(ColorTargetState {
+                format: IRRADIANCE_FORMAT,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            }));
+            descriptor
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
 
         Self {
-            cached_id,
+            cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_main_pass_pipeline",
+                TextureFormat::bevy_default(),
+            )),
+            hdr_cached_id: pipeline_cache.queue_render_pipeline(descriptor(
+                "light_2d_main_pass_hdr_pipeline",
+                ViewTarget::TEXTURE_FORMAT_HDR,
+            )),
+            #[cfg(feature = "radiance_cascades")]
+            radiance_cascades_cached_id: pipeline_cache.queue_render_pipeline(
+                radiance_cascades_descriptor(
+                    "light_2d_main_pass_radiance_cascades_pipeline",
+                    TextureFormat::bevy_default(),
+                ),
+            ),
+            #[cfg(feature = "radiance_cascades")]
+            hdr_radiance_cascades_cached_id: pipeline_cache.queue_render_pipeline(
+                radiance_cascades_descriptor(
+                    "light_2d_main_pass_radiance_cascades_hdr_pipeline",
+                    ViewTarget::TEXTURE_FORMAT_HDR,
+                ),
+            ),
             main_pass_layout,
+            #[cfg(feature = "radiance_cascades")]
+            global_illumination_layout,
             main_texture_sampler,
         }
     }
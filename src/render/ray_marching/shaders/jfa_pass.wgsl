@@ -16,14 +16,14 @@ var<uniform> jfa_iter: u32;
 fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     let px = invocation_id.xy;
 
-    if px.x >= sdf_meta.size.x || px.y >= sdf_meta.size.x {
+    if px.x >= sdf_meta.size.x || px.y >= sdf_meta.size.y {
         return;
     }
 
-    var best_dist_interior = 9999999.;
-    var best_px_interior = vec2f(0.);
-    var best_dist_exterior = 9999999.;
-    var best_px_exterior = vec2f(0.);
+    var best_dist_opaque = 9999999.;
+    var best_px_opaque = vec2f(0.);
+    var best_dist_translucent = 9999999.;
+    var best_px_translucent = vec2f(0.);
 
     let step = i32(max(max(sdf_meta.size.x, sdf_meta.size.y) >> (jfa_iter + 1u), 1u));
     let tex_isize = vec2i(sdf_meta.size);
@@ -35,21 +35,21 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
             
             if neighbor_data.x > 0. && neighbor_data.y > 0. {
                 let d = distance(vec2f(px), vec2f(neighbor_data.xy));
-                if d < best_dist_interior {
-                    best_dist_interior = d;
-                    best_px_interior = neighbor_data.xy;
+                if d < best_dist_opaque {
+                    best_dist_opaque = d;
+                    best_px_opaque = neighbor_data.xy;
                 }
             }
 
             if neighbor_data.z > 0. && neighbor_data.w > 0. {
                 let d = distance(vec2f(px), vec2f(neighbor_data.zw));
-                if d < best_dist_exterior {
-                    best_dist_exterior = d;
-                    best_px_exterior = neighbor_data.zw;
+                if d < best_dist_translucent {
+                    best_dist_translucent = d;
+                    best_px_translucent = neighbor_data.zw;
                 }
             }
         }
     }
 
-    textureStore(dest_sdf_tex, px, vec4f(best_px_interior, best_px_exterior));
+    textureStore(dest_sdf_tex, px, vec4f(best_px_opaque, best_px_translucent));
 }
@@ -9,19 +9,25 @@ var sdf_tex: texture_storage_2d<rgba32float, write>;
 @group(0) @binding(2)
 var<uniform> sdf_meta: SdfMeta;
 
+@group(0) @binding(3)
+var translucent_tex: texture_2d<f32>;
+
 @compute @workgroup_size(16, 16, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     let px = invocation_id.xy;
 
-    if px.x >= sdf_meta.size.x || px.y >= sdf_meta.size.x {
+    if px.x >= sdf_meta.size.x || px.y >= sdf_meta.size.y {
         return;
     }
 
+    // Opaque casters are seeded in rg and translucent casters in ba, so the sdf holds
+    // the distance to both. The translucent texture is cleared to white.
+    var seeds = vec4f(0.);
     if textureLoad(main_tex, px, 0).a > sdf_meta.alpha_threshold {
-        // interior pixels
-        textureStore(sdf_tex, px, vec4<f32>(vec2f(px), 0., 0.));
-    } else {
-        // exterior pixels
-        textureStore(sdf_tex, px, vec4<f32>(0., 0., vec2f(px)));
+        seeds = vec4f(vec2f(px), seeds.zw);
+    }
+    if any(textureLoad(translucent_tex, px, 0).rgb < vec3f(1.)) {
+        seeds = vec4f(seeds.xy, vec2f(px));
     }
+    textureStore(sdf_tex, px, seeds);
 }
@@ -2,8 +2,12 @@
 #import bevy_render::view::View
 #import bevy_incandescent::{
     ray_marching::types::SdfMeta,
-    math::{is_point_inside_sector},
-    types::{AmbientLight2d, PointLight2d},
+    lighting::{
+        get_cookie_uv, get_elliptical_rel, get_normal_shading, get_point_light_falloff,
+        get_specular_shading, screen_to_world, NO_LIGHT_COOKIE,
+    },
+    math::{closest_point_on_segment, get_sector_falloff, is_point_inside_sector},
+    types::{AmbientLight2d, DirectionalLight2d, LineLight2d, PointLight2d},
 }
 
 @group(0) @binding(0)
@@ -27,29 +31,147 @@ var<uniform> ambient_light: AmbientLight2d;
 @group(0) @binding(6)
 var<storage> point_lights: array<PointLight2d>;
 
-fn ray_marching(px: vec2f, dir: vec2f, center: vec2f, radius: f32) -> bool {
+@group(0) @binding(7)
+var<storage> directional_lights: array<DirectionalLight2d>;
+
+@group(0) @binding(8)
+var<storage> line_lights: array<LineLight2d>;
+
+@group(0) @binding(9)
+var light_cookies: texture_2d_array<f32>;
+
+@group(0) @binding(10)
+var light_cookie_sampler: sampler;
+
+@group(0) @binding(12)
+var surface_normal: texture_2d<f32>;
+
+@group(0) @binding(13)
+var surface_material: texture_2d<f32>;
+
+@group(0) @binding(14)
+var surface_emissive: texture_2d<f32>;
+
+@group(0) @binding(15)
+var translucent_tex: texture_2d<f32>;
+
+#ifdef RADIANCE_CASCADES
+@group(1) @binding(0)
+var global_illumination: texture_2d<f32>;
+
+struct FragmentOutput {
+    @location(0) color: vec4f,
+    // The light received by each pixel, bounced off casters in the next frame.
+    @location(1) irradiance: vec4f,
+}
+#endif
+
+// Returns the visibility of the light from px, tinted by the translucent casters in between.
+// https://iquilezles.org/articles/rmshadows/
+// The penumbra is estimated from the closest distance to casters along the ray,
+// compared to the cone from px to the disk of the light.
+fn ray_marching(px: vec2f, dir: vec2f, center: vec2f, light_radius: f32) -> vec3f {
     var current = px;
     let tex_fsize = vec2f(sdf_meta.size);
+    let light_dist = distance(px, center);
+    let penumbra = light_radius * sdf_meta.softness;
+    var visibility = 1.;
+    var transmittance = vec3f(1.);
+    var in_translucent = false;
 
     while current.x > 0. && current.x < tex_fsize.x
           && current.y > 0. && current.y < tex_fsize.y {
         let sdf_data = textureLoad(sdf_tex, vec2i(current)).rg;
         let closest = sdf_data.r;
         if closest < 0.1 {
-            return false;
+            return vec3f(0.);
+        }
+
+        let travelled = distance(px, current);
+        if penumbra > 0. && travelled > 0.1 {
+            visibility = min(visibility, closest * light_dist / (travelled * penumbra));
+        }
+
+        var step = min(closest, distance(current, center));
+        // Rays step up to translucent casters through the sdf, and only take short steps
+        // inside them, accumulating their transmittance once per caster entered.
+        let is_translucent = sdf_data.g < 0.5;
+        if is_translucent {
+            if !in_translucent {
+                transmittance *= textureLoad(translucent_tex, vec2i(current), 0).rgb;
+            }
+            step = min(step, sdf_meta.translucent_step);
+        } else {
+            step = min(step, sdf_data.g);
         }
-        current += dir * min(closest, distance(current, center));
+        in_translucent = is_translucent;
+
+        current += dir * step;
         if distance(current, center) < 0.1 {
-            return true;
+            break;
         }
     }
+    return smoothstep(0., 1., visibility) * transmittance;
+}
+
+fn ray_marching_directional(px: vec2f, dir: vec2f, max_dist: f32) -> bool {
+    var current = px;
+    var travelled = 0.;
+    let tex_fsize = vec2f(sdf_meta.size);
+
+    while travelled < max_dist
+          && current.x > 0. && current.x < tex_fsize.x
+          && current.y > 0. && current.y < tex_fsize.y {
+        let closest = textureLoad(sdf_tex, vec2i(current)).r;
+        if closest < 0.1 {
+            return false;
+        }
+        let step = min(closest, max_dist - travelled);
+        current += dir * step;
+        travelled += step;
+    }
     return true;
 }
 
+fn get_cookie_color(light: PointLight2d, rel_ss: vec2f, range_ss: f32) -> vec3f {
+    if light.cookie_index == NO_LIGHT_COOKIE {
+        return vec3f(1.);
+    }
+
+    let uv = get_cookie_uv(rel_ss, range_ss, light.rotation);
+    let cookie = textureSampleLevel(light_cookies, light_cookie_sampler, uv, light.cookie_index, 0.);
+    return cookie.rgb * cookie.a;
+}
+
+// Samples points evenly on the segment, so longer segments produce wider penumbra.
+fn line_light_visibility(px: vec2f, start: vec2f, end: vec2f) -> vec3f {
+    let samples = max(sdf_meta.line_light_samples, 1u);
+    var visibility = vec3f(0.);
+
+    for (var i = 0u; i < samples; i++) {
+        let target_ss = mix(start, end, (f32(i) + 0.5) / f32(samples));
+        if distance(px, target_ss) < 0.1 {
+            visibility += vec3f(1.);
+        } else {
+            visibility += ray_marching(px, normalize(target_ss - px), target_ss, 0.);
+        }
+    }
+    return visibility / f32(samples);
+}
+
 @fragment
+#ifdef RADIANCE_CASCADES
+fn fragment(in: FullscreenVertexOutput) -> FragmentOutput {
+#else
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
+#endif
     let screen_size = 2. * vec2f(main_view.inverse_projection[0][0], main_view.inverse_projection[1][1]);
-    let px = vec2i(in.uv * vec2f(screen_size));
+    let px = in.uv * screen_size;
+    // Lights are in world units while the SDF is in physical pixels.
+    let to_sdf = vec2f(sdf_meta.size) / screen_size;
+    let px_sdf = in.uv * vec2f(sdf_meta.size);
+    let surface = textureSample(surface_normal, main_tex_sampler, in.uv);
+    let material = textureSample(surface_material, main_tex_sampler, in.uv);
 
     var color = vec3f(0.);
     for (var i_light = 0u; i_light < arrayLength(&point_lights); i_light++) {
@@ -57,19 +179,69 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
         let light_range_ss = max((*light).range_ss * screen_size.x, 0.);
         let light_radius_ss = max((*light).radius_ss * screen_size.x, 0.);
         let light_pos_ss = (*light).position_ss * screen_size;
-        let dir = normalize(light_pos_ss - vec2f(px));
+        let dir_sdf = normalize((light_pos_ss - px) * to_sdf);
+
+        // Sectors are defined in world space where y points upwards.
+        let rel_ws = screen_to_world(px - light_pos_ss, main_view.view);
+        let rel_range_ws = get_elliptical_rel(rel_ws, (*light).range_scale, (*light).range_rotation);
 
-        if is_point_inside_sector(vec2f(px), light_pos_ss, light_range_ss, (*light).angles) {
-            if ray_marching(vec2f(px), dir, light_pos_ss, light_range_ss) {
-                let atten = saturate(
-                    (distance(vec2f(px), light_pos_ss) - light_radius_ss) / (light_range_ss - light_radius_ss)
+        if is_point_inside_sector(rel_range_ws, vec2f(0.), light_range_ss, (*light).angles) {
+            let visibility = ray_marching(px_sdf, dir_sdf, light_pos_ss * to_sdf, light_radius_ss * to_sdf.x);
+            if any(visibility > vec3f(0.)) {
+                let falloff = get_point_light_falloff(
+                    *light, length(rel_range_ws), light_radius_ss, light_range_ss
                 );
-                color += (*light).color.rgb * (1. - atten);
+                let cookie_color = get_cookie_color(*light, rel_range_ws * vec2f(1., -1.), light_range_ss);
+                let sector_falloff = get_sector_falloff(rel_range_ws, (*light).angles, (*light).inner_extent);
+                let light_height_ss = max((*light).height_ss * screen_size.x, 0.);
+                let surface_shading = get_normal_shading(surface, -rel_ws, light_height_ss)
+                                      + get_specular_shading(surface, material, -rel_ws, light_height_ss);
+                color += (*light).color.rgb * (*light).intensity * visibility * falloff * sector_falloff * surface_shading * cookie_color;
             }
         }
     }
-    
-    return textureSample(main_tex, main_tex_sampler, in.uv)
-           * vec4f(ambient_light.color.rgb * ambient_light.intensity, 1.)
-           + vec4f(color, 0.);
+
+    for (var i_light = 0u; i_light < arrayLength(&line_lights); i_light++) {
+        let light = &line_lights[i_light];
+        let start_ss = (*light).start_ss * screen_size;
+        let end_ss = (*light).end_ss * screen_size;
+        let thickness_ss = max((*light).thickness_ss * screen_size.x, 0.);
+        let range_ss = max((*light).range_ss * screen_size.x, 0.);
+
+        let dist = distance(px, closest_point_on_segment(px, start_ss, end_ss));
+        if dist > range_ss {
+            continue;
+        }
+
+        let atten = saturate((dist - thickness_ss) / (range_ss - thickness_ss));
+        let visibility = line_light_visibility(px_sdf, start_ss * to_sdf, end_ss * to_sdf);
+        color += (*light).color.rgb * (*light).intensity * (1. - atten) * visibility;
+    }
+
+    for (var i_light = 0u; i_light < arrayLength(&directional_lights); i_light++) {
+        let light = &directional_lights[i_light];
+        let shadow_length_ss = max((*light).shadow_length_ss * screen_size.x, 0.);
+
+        let shadow_sdf = -(*light).direction_ss * shadow_length_ss * to_sdf;
+
+        if ray_marching_directional(px_sdf, normalize(shadow_sdf), length(shadow_sdf)) {
+            color += (*light).color.rgb * (*light).intensity;
+        }
+    }
+
+#ifdef RADIANCE_CASCADES
+    color += textureSample(global_illumination, main_tex_sampler, in.uv).rgb;
+#endif
+
+    let receiver = max(material.b, 0.);
+    let ambient = mix(vec3f(1.), ambient_light.color.rgb * ambient_light.intensity, saturate(receiver));
+    let lit = textureSample(main_tex, main_tex_sampler, in.uv) * vec4f(ambient, 1.)
+              + vec4f(color * receiver, 0.)
+              + vec4f(textureSample(surface_emissive, main_tex_sampler, in.uv).rgb, 0.);
+
+#ifdef RADIANCE_CASCADES
+    return FragmentOutput(lit, vec4f(color, 1.));
+#else
+    return lit;
+#endif
 }
@@ -14,9 +14,8 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3u) {
     }
 
     let px_data = textureLoad(sdf_tex, px);
-    let interior_closest = distance(vec2f(px), textureLoad(sdf_tex, px).rg);
-    let exterior_closest = distance(vec2f(px), textureLoad(sdf_tex, px).ba);
-    // let interior_closest = distance(vec2f(px) / vec2f(sdf_meta.size), textureLoad(sdf_tex, px).rg / vec2f(sdf_meta.size));
-    // let exterior_closest = distance(vec2f(px) / vec2f(sdf_meta.size), textureLoad(sdf_tex, px).ba / vec2f(sdf_meta.size));
-    textureStore(sdf_tex, px, vec4f(interior_closest, exterior_closest, 0., 1.));
+    let opaque_closest = distance(vec2f(px), px_data.rg);
+    // No seed was found when there's no translucent caster at all.
+    let translucent_closest = select(distance(vec2f(px), px_data.ba), 1e9, all(px_data.ba == vec2f(0.)));
+    textureStore(sdf_tex, px, vec4f(opaque_closest, translucent_closest, 0., 1.));
 }
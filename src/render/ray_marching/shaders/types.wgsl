@@ -4,4 +4,7 @@ struct SdfMeta {
     size: vec2u,
     alpha_threshold: f32,
     edge_lighting: f32,
+    line_light_samples: u32,
+    softness: f32,
+    translucent_step: f32,
 }
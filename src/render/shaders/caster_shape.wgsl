@@ -0,0 +1,14 @@
+#import bevy_render::view::View
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+@vertex
+fn vertex(@location(0) position: vec3f) -> @builtin(position) vec4f {
+    return view.view_proj * vec4f(position, 1.);
+}
+
+@fragment
+fn fragment() -> @location(0) vec4f {
+    return vec4f(0., 0., 0., 1.);
+}
@@ -0,0 +1,12 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0)
+var source_tex: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
+    return textureSample(source_tex, source_sampler, in.uv);
+}
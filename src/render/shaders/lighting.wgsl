@@ -1,5 +1,11 @@
 #define_import_path bevy_incandescent::lighting
 
+#import bevy_incandescent::types::PointLight2d
+
+// Samples of all custom falloff curves, each lighting pass binds them at its own index.
+@group(0) @binding(#{FALLOFF_CURVES_BINDING})
+var<storage> falloff_curves: array<f32>;
+
 // The same as bevy_pbr::src::render::pbr_lighting
 fn get_distance_attenuation(distance_square: f32, inverse_range_squared: f32) -> f32 {
     let factor = distance_square * inverse_range_squared;
@@ -7,3 +13,109 @@ fn get_distance_attenuation(distance_square: f32, inverse_range_squared: f32) ->
     let attenuation = smooth_factor * smooth_factor;
     return attenuation * 1.0 / max(distance_square, 0.0001);
 }
+
+const LIGHT_FALLOFF_LINEAR: u32 = 0u;
+const LIGHT_FALLOFF_SMOOTH: u32 = 1u;
+const LIGHT_FALLOFF_INVERSE_SQUARE: u32 = 2u;
+const LIGHT_FALLOFF_CUSTOM: u32 = 3u;
+
+// Returns the intensity of the light at the given distance, custom curves are not handled here.
+fn get_light_falloff(falloff: u32, dist: f32, radius: f32, range: f32) -> f32 {
+    let t = saturate((dist - radius) / (range - radius));
+
+    switch falloff {
+        case LIGHT_FALLOFF_SMOOTH: {
+            return 1. - smoothstep(0., 1., t);
+        }
+        case LIGHT_FALLOFF_INVERSE_SQUARE: {
+            // Normalized so that the light is fully lit inside the radius.
+            let r = max(radius, 1.);
+            let d = max(dist, r);
+            return saturate(get_distance_attenuation(d * d, 1. / (range * range)) * r * r);
+        }
+        default: {
+            return 1. - t;
+        }
+    }
+}
+
+// Returns the intensity of a point light at the given distance, including custom curves.
+fn get_point_light_falloff(light: PointLight2d, dist: f32, radius: f32, range: f32) -> f32 {
+    if light.falloff != LIGHT_FALLOFF_CUSTOM || light.falloff_curve_len == 0u {
+        return get_light_falloff(light.falloff, dist, radius, range);
+    }
+
+    let last = light.falloff_curve_len - 1u;
+    let t = saturate((dist - radius) / (range - radius)) * f32(last);
+    let i = min(u32(t), last);
+    let next = min(i + 1u, last);
+    return mix(
+        falloff_curves[light.falloff_curve_offset + i],
+        falloff_curves[light.falloff_curve_offset + next],
+        fract(t),
+    );
+}
+
+// Lambert shading of normal mapped sprites, 1 for everything else.
+// `surface` is sampled from the normal buffer, `to_light` and `height` are in pixels
+// with y pointing upwards.
+fn get_normal_shading(surface: vec4f, to_light: vec2f, height: f32) -> f32 {
+    if surface.b <= 0. {
+        return 1.;
+    }
+
+    let light_dir = normalize(vec3f(to_light, max(height, 0.0001)));
+    return mix(1., saturate(dot(get_surface_normal(surface), light_dir)), surface.b);
+}
+
+// Blinn-Phong highlight seen from right above the sprites.
+// `material` is sampled from the material buffer, specular intensity in r and glossiness in g.
+fn get_specular_shading(surface: vec4f, material: vec4f, to_light: vec2f, height: f32) -> f32 {
+    if material.r <= 0. {
+        return 0.;
+    }
+
+    let normal = get_surface_normal(surface);
+    let light_dir = normalize(vec3f(to_light, max(height, 0.0001)));
+    if dot(normal, light_dir) <= 0. {
+        return 0.;
+    }
+
+    let half_dir = normalize(light_dir + vec3f(0., 0., 1.));
+    let shininess = exp2(material.g * 10. + 1.);
+    return material.r * pow(saturate(dot(normal, half_dir)), shininess);
+}
+
+fn get_surface_normal(surface: vec4f) -> vec3f {
+    let normal_xy = surface.rg * 2. - 1.;
+    return vec3f(normal_xy, sqrt(saturate(1. - dot(normal_xy, normal_xy))));
+}
+
+// Turns a vector in screen space, where y points downwards, into world space
+// by undoing the rotation of the camera.
+fn screen_to_world(v: vec2f, world_from_view: mat4x4f) -> vec2f {
+    return normalize(world_from_view[0].xy) * v.x - normalize(world_from_view[1].xy) * v.y;
+}
+
+// Stretches the position relative to a light in world space along the axes of its
+// elliptical range, so that the ellipse becomes the circle of its range.
+// Circular lights have a scale of 1 and are left unchanged.
+fn get_elliptical_rel(rel_ws: vec2f, range_scale: vec2f, range_rotation: f32) -> vec2f {
+    let c = cos(range_rotation);
+    let s = sin(range_rotation);
+    let local = vec2f(c * rel_ws.x + s * rel_ws.y, -s * rel_ws.x + c * rel_ws.y) / range_scale;
+    return vec2f(c * local.x - s * local.y, s * local.x + c * local.y);
+}
+
+const NO_LIGHT_COOKIE: u32 = 0xffffffffu;
+
+// Maps the position relative to the light into the uv of its cookie,
+// which covers the whole range of the light and rotates with it.
+fn get_cookie_uv(rel_ss: vec2f, range_ss: f32, rotation: f32) -> vec2f {
+    // Screen space y points downwards while rotation is counter clockwise in world space.
+    let c = cos(rotation);
+    let s = sin(rotation);
+    let rel_ws = rel_ss * vec2f(1., -1.) / range_ss;
+    let local = vec2f(c * rel_ws.x + s * rel_ws.y, -s * rel_ws.x + c * rel_ws.y);
+    return vec2f(local.x, -local.y) * 0.5 + 0.5;
+}
@@ -33,3 +33,26 @@ fn is_point_inside_sector(
         return d >= 0.;
     }
 }
+
+// Returns 1 inside the inner sector, fading out smoothly to 0 at the edge of the sector.
+// The same as `CircularSector::angular_falloff`.
+fn get_sector_falloff(point: vec2f, angles: array<f32, 2>, inner_extent: f32) -> f32 {
+    if inner_extent >= angles[1] || dot(point, point) < 0.0001 {
+        return 1.;
+    }
+
+    let ctr = vec2f(cos(angles[0]), sin(angles[0]));
+    let angle = acos(clamp(dot(normalize(point), ctr), -1., 1.));
+    return 1. - smoothstep(inner_extent, angles[1], angle);
+}
+
+fn closest_point_on_segment(point: vec2f, start: vec2f, end: vec2f) -> vec2f {
+    let seg = end - start;
+    let len_sq = dot(seg, seg);
+    if len_sq < 0.0001 {
+        return start;
+    }
+
+    let t = saturate(dot(point - start, seg) / len_sq);
+    return start + seg * t;
+}
@@ -0,0 +1,56 @@
+#import bevy_render::view::View
+
+struct Occluder2d {
+    model: mat4x4f,
+    // Offset in xy and scale in zw.
+    uv_rect: vec4f,
+    color: vec4f,
+}
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+@group(1) @binding(0)
+var occluder_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var occluder_sampler: sampler;
+
+@group(2) @binding(0)
+var<uniform> occluder: Occluder2d;
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) uv: vec2f,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2f, 6>(
+        vec2f(-0.5, -0.5),
+        vec2f(0.5, -0.5),
+        vec2f(0.5, 0.5),
+        vec2f(-0.5, -0.5),
+        vec2f(0.5, 0.5),
+        vec2f(-0.5, 0.5),
+    );
+    let corner = corners[index];
+
+    var out: VertexOutput;
+    out.position = view.view_proj * occluder.model * vec4f(corner, 0., 1.);
+    // The y axis of textures points downwards.
+    out.uv = occluder.uv_rect.xy + vec2f(corner.x + 0.5, 0.5 - corner.y) * occluder.uv_rect.zw;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4f {
+    return textureSample(occluder_texture, occluder_sampler, in.uv) * occluder.color;
+}
+
+// Transmittance in rgb, multiplied into the transmittance target.
+@fragment
+fn translucent_fragment(in: VertexOutput) -> @location(0) vec4f {
+    let color = textureSample(occluder_texture, occluder_sampler, in.uv) * occluder.color;
+    return vec4f(mix(vec3f(1.), color.rgb, color.a), 0.);
+}
@@ -0,0 +1,99 @@
+#import bevy_render::view::View
+
+struct Surface2d {
+    model: mat4x4f,
+    // Offset in xy and scale in zw.
+    uv_rect: vec4f,
+    // -1 on flipped axes.
+    flip: vec2f,
+    flags: u32,
+    emissive: vec4f,
+    light_receiver: f32,
+}
+
+const SURFACE_FLAG_NORMAL_MAP: u32 = 1u;
+const SURFACE_FLAG_SPECULAR_MAP: u32 = 2u;
+const SURFACE_FLAG_EMISSIVE_MAP: u32 = 4u;
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+@group(1) @binding(0)
+var sprite_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var normal_map: texture_2d<f32>;
+
+@group(1) @binding(2)
+var specular_map: texture_2d<f32>;
+
+@group(1) @binding(3)
+var emissive_map: texture_2d<f32>;
+
+@group(1) @binding(4)
+var surface_sampler: sampler;
+
+@group(2) @binding(0)
+var<uniform> surface: Surface2d;
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) uv: vec2f,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2f, 6>(
+        vec2f(-0.5, -0.5),
+        vec2f(0.5, -0.5),
+        vec2f(0.5, 0.5),
+        vec2f(-0.5, -0.5),
+        vec2f(0.5, 0.5),
+        vec2f(-0.5, 0.5),
+    );
+    let corner = corners[index];
+
+    var out: VertexOutput;
+    out.position = view.view_proj * surface.model * vec4f(corner, 0., 1.);
+    // The y axis of textures points downwards.
+    out.uv = surface.uv_rect.xy + vec2f(corner.x + 0.5, 0.5 - corner.y) * surface.uv_rect.zw;
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) normal: vec4f,
+    @location(1) material: vec4f,
+    @location(2) emissive: vec4f,
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    let coverage = textureSample(sprite_texture, surface_sampler, in.uv).a;
+    let normal_ts = textureSample(normal_map, surface_sampler, in.uv).xyz * 2. - 1.;
+    let specular = textureSample(specular_map, surface_sampler, in.uv).rg;
+    let emissive = textureSample(emissive_map, surface_sampler, in.uv).rgb;
+
+    var out: FragmentOutput;
+    out.normal = vec4f(0.5, 0.5, 0., coverage);
+    out.material = vec4f(0., 0., surface.light_receiver, coverage);
+    out.emissive = vec4f(surface.emissive.rgb, coverage);
+
+    if (surface.flags & SURFACE_FLAG_NORMAL_MAP) != 0u {
+        // Rotate the normal from the tangent space into world space, following the sprite.
+        let tangent = normalize(surface.model[0].xy);
+        let bitangent = normalize(surface.model[1].xy);
+        let normal_xy = normal_ts.xy * surface.flip;
+        let normal = normalize(vec3f(tangent * normal_xy.x + bitangent * normal_xy.y, normal_ts.z));
+        out.normal = vec4f(normal.xy * 0.5 + 0.5, 1., coverage);
+    }
+
+    if (surface.flags & SURFACE_FLAG_SPECULAR_MAP) != 0u {
+        out.material = vec4f(specular, surface.light_receiver, coverage);
+    }
+
+    if (surface.flags & SURFACE_FLAG_EMISSIVE_MAP) != 0u {
+        out.emissive = vec4f(surface.emissive.rgb * emissive, coverage);
+    }
+
+    return out;
+}
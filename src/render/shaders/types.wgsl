@@ -15,6 +15,34 @@ struct PointLight2d {
     position_ss: vec2f,
     radius_ss: f32,
     range_ss: f32,
+    height_ss: f32,
     color: vec4f,
     angles: array<f32, 2>,
+    inner_extent: f32,
+    // u32::MAX if the light has no cookie.
+    cookie_index: u32,
+    rotation: f32,
+    falloff: u32,
+    falloff_curve_offset: u32,
+    falloff_curve_len: u32,
+    // The elliptical range along its axes divided by range_ss, vec2f(1.) for circular lights.
+    range_scale: vec2f,
+    // The rotation of the axes of the elliptical range in world space.
+    range_rotation: f32,
+}
+
+struct LineLight2d {
+    intensity: f32,
+    start_ss: vec2f,
+    end_ss: vec2f,
+    thickness_ss: f32,
+    range_ss: f32,
+    color: vec4f,
+}
+
+struct DirectionalLight2d {
+    color: vec4f,
+    intensity: f32,
+    direction_ss: vec2f,
+    shadow_length_ss: f32,
 }
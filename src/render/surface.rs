@@ -0,0 +1,572 @@
+use bevy::{
+    asset::{AssetEvent, AssetId},
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashSet},
+        query::{Has, Or, QueryState, With},
+        system::{lifetimeless::Read, Commands, Query, Res, ResMut, Resource, SystemParam},
+        world::{FromWorld, World},
+    },
+    math::{Mat4, UVec2, Vec2, Vec4, Vec4Swizzles},
+    render::{
+        color::Color,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_resource::{
+            AddressMode, BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            BlendState, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            DynamicUniformBuffer, Extent3d, FilterMode, FragmentState, MultisampleState,
+            PipelineCache, PrimitiveState, RenderPassDescriptor, RenderPipelineDescriptor, Sampler,
+            SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, VertexState,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{ColorAttachment, Image, TextureCache},
+        view::{
+            ExtractedView, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms,
+            VisibleEntities,
+        },
+        Extract,
+    },
+    sprite::{ExtractedSprite, ExtractedSprites, SpriteAssetEvents},
+    utils::{HashMap, HashSet},
+};
+
+use bevy::render::render_resource::binding_types as binding;
+
+use crate::ecs::{Emissive2d, LightReceiver2d, NormalMap2d, SpecularMap2d, Unlit2d};
+
+use super::SURFACE_SHADER;
+
+pub const SURFACE_NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const SURFACE_MATERIAL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const SURFACE_EMISSIVE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const SURFACE_FLAG_NORMAL_MAP: u32 = 1;
+pub const SURFACE_FLAG_SPECULAR_MAP: u32 = 1 << 1;
+pub const SURFACE_FLAG_EMISSIVE_MAP: u32 = 1 << 2;
+
+#[derive(RenderLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Surface2dPass;
+
+#[derive(Component, Clone, Copy)]
+pub struct ExtractedSurface2d {
+    pub normal_map: Option<AssetId<Image>>,
+    pub specular_map: Option<AssetId<Image>>,
+    /// Linear color multiplied by the intensity.
+    pub emissive: Vec4,
+    pub emissive_map: Option<AssetId<Image>>,
+    pub light_receiver: f32,
+}
+
+type ExtractSurface = (
+    Entity,
+    Option<&'static NormalMap2d>,
+    Option<&'static SpecularMap2d>,
+    Option<&'static Emissive2d>,
+    Option<&'static LightReceiver2d>,
+    Has<Unlit2d>,
+);
+
+type SurfaceFilter = Or<(
+    With<NormalMap2d>,
+    With<SpecularMap2d>,
+    With<Emissive2d>,
+    With<LightReceiver2d>,
+    With<Unlit2d>,
+)>;
+
+pub fn extract_surfaces(
+    mut commands: Commands,
+    surfaces_query: Extract<Query<ExtractSurface, SurfaceFilter>>,
+) {
+    commands.insert_or_spawn_batch(
+        surfaces_query
+            .iter()
+            .map(
+                |(entity, normal_map, specular_map, emissive, receiver, unlit)| {
+                    (
+                        entity,
+                        ExtractedSurface2d {
+                            normal_map: normal_map.map(|map| map.0.id()),
+                            specular_map: specular_map.map(|map| map.0.id()),
+                            emissive: emissive.map_or(Vec4::ZERO, |emissive| {
+                                Vec4::from_array(emissive.color.as_linear_rgba_f32())
+                                    * emissive.intensity
+                            }),
+                            emissive_map: emissive
+                                .and_then(|emissive| emissive.texture.as_ref())
+                                .map(|texture| texture.id()),
+                            light_receiver: match (unlit, receiver) {
+                                (true, _) => 0.,
+                                (false, Some(receiver)) => receiver.multiplier,
+                                (false, None) => 1.,
+                            },
+                        },
+                    )
+                },
+            )
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[derive(ShaderType)]
+pub struct GpuSurface2d {
+    pub model: Mat4,
+    /// Offset in xy and scale in zw.
+    pub uv_rect: Vec4,
+    /// -1 on flipped axes.
+    pub flip: Vec2,
+    pub flags: u32,
+    pub emissive: Vec4,
+    pub light_receiver: f32,
+}
+
+#[derive(Resource)]
+pub struct Surface2dPipeline {
+    pub cached_id: CachedRenderPipelineId,
+    pub view_layout: BindGroupLayout,
+    pub texture_layout: BindGroupLayout,
+    pub surface_layout: BindGroupLayout,
+    pub sampler: Sampler,
+}
+
+impl FromWorld for Surface2dPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let view_layout = render_device.create_bind_group_layout(
+            "surface_2d_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                binding::uniform_buffer::<ViewUniform>(true),
+            ),
+        );
+
+        let texture_layout = render_device.create_bind_group_layout(
+            "surface_2d_texture_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // Sprite texture
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Normal map
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Specular map
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    // Emissive map
+                    binding::texture_2d(TextureSampleType::Float { filterable: true }),
+                    binding::sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let surface_layout = render_device.create_bind_group_layout(
+            "surface_2d_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                binding::uniform_buffer::<GpuSurface2d>(true),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("surface_2d_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let cached_id =
+            world
+                .resource::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("surface_2d_pipeline".into()),
+                    layout: vec![
+                        view_layout.clone(),
+                        texture_layout.clone(),
+                        surface_layout.clone(),
+                    ],
+                    push_constant_ranges: vec![],
+                    vertex: VertexState {
+                        shader: SURFACE_SHADER,
+                        shader_defs: vec![],
+                        entry_point: "vertex".into(),
+                        buffers: vec![],
+                    },
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    fragment: Some(FragmentState {
+                        shader: SURFACE_SHADER,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![
+                            Some(ColorTargetState {
+                                format: SURFACE_NORMAL_FORMAT,
+                                blend: Some(BlendState::ALPHA_BLENDING),
+                                write_mask: ColorWrites::ALL,
+                            }),
+                            Some(ColorTargetState {
+                                format: SURFACE_MATERIAL_FORMAT,
+                                blend: Some(BlendState::ALPHA_BLENDING),
+                                write_mask: ColorWrites::ALL,
+                            }),
+                            Some(ColorTargetState {
+                                format: SURFACE_EMISSIVE_FORMAT,
+                                blend: Some(BlendState::ALPHA_BLENDING),
+                                write_mask: ColorWrites::ALL,
+                            }),
+                        ],
+                    }),
+                });
+
+        Self {
+            cached_id,
+            view_layout,
+            texture_layout,
+            surface_layout,
+            sampler,
+        }
+    }
+}
+
+/// The sprite texture followed by its normal, specular and emissive maps.
+type SurfaceTextures = (
+    AssetId<Image>,
+    Option<AssetId<Image>>,
+    Option<AssetId<Image>>,
+    Option<AssetId<Image>>,
+);
+
+#[derive(Resource, Default)]
+pub struct Surface2dBuffer {
+    buffer: DynamicUniformBuffer<GpuSurface2d>,
+    bind_group: Option<BindGroup>,
+    /// Kept across frames while the textures are drawn and unchanged.
+    texture_bind_groups: HashMap<SurfaceTextures, BindGroup>,
+}
+
+pub struct SurfaceDraw2d {
+    pub texture_bind_group: BindGroup,
+    pub dynamic_offset: u32,
+}
+
+/// Screen space buffers of the surfaces of all sprites in the view.
+///
+/// The normal buffer stores xy of the world space normal in rg and whether the
+/// sprite is normal mapped in b, all blended by the coverage of the sprites.
+/// The material buffer stores the specular intensity in r, the glossiness in g and
+/// the multiplier of [`LightReceiver2d`] in b.
+/// The emissive buffer stores the emitted color in rgb, which can exceed 1.
+///
+/// Without any [`ExtractedSurface2d`] in the view, the buffers are a single pixel
+/// holding their clear values and nothing is drawn.
+#[derive(Component)]
+pub struct ViewSurfaces2d {
+    pub normal: ColorAttachment,
+    pub material: ColorAttachment,
+    pub emissive: ColorAttachment,
+    pub draws: Vec<SurfaceDraw2d>,
+}
+
+/// Returns the model matrix of the unit quad centered at the origin,
+/// the uv rect (offset in xy and scale in zw) and the flip (-1 on flipped axes) of the sprite.
+pub fn compute_sprite_quad(sprite: &ExtractedSprite, image_size: Vec2) -> (Mat4, Vec4, Vec2) {
+    let (uv_offset, uv_size, size) = match sprite.rect {
+        Some(rect) => (rect.min / image_size, rect.size() / image_size, rect.size()),
+        None => (Vec2::ZERO, Vec2::ONE, image_size),
+    };
+    let size = sprite.custom_size.unwrap_or(size);
+
+    let mut uv_rect = Vec4::new(uv_offset.x, uv_offset.y, uv_size.x, uv_size.y);
+    let mut flip = Vec2::ONE;
+    if sprite.flip_x {
+        uv_rect.x += uv_rect.z;
+        uv_rect.z = -uv_rect.z;
+        flip.x = -1.;
+    }
+    if sprite.flip_y {
+        uv_rect.y += uv_rect.w;
+        uv_rect.w = -uv_rect.w;
+        flip.y = -1.;
+    }
+
+    let model = sprite.transform.compute_matrix()
+        * Mat4::from_translation((-sprite.anchor * size).extend(0.))
+        * Mat4::from_scale(size.extend(1.));
+
+    (model, uv_rect, flip)
+}
+
+#[derive(SystemParam)]
+pub struct SurfaceResources<'w> {
+    images: Res<'w, RenderAssets<Image>>,
+    image_events: Res<'w, SpriteAssetEvents>,
+    pipeline: Res<'w, Surface2dPipeline>,
+    texture_cache: ResMut<'w, TextureCache>,
+    render_device: Res<'w, RenderDevice>,
+    render_queue: Res<'w, RenderQueue>,
+}
+
+pub fn prepare_surfaces(
+    mut commands: Commands,
+    main_views: Query<(Entity, &ExtractedView, &VisibleEntities), With<ViewTarget>>,
+    extracted_sprites: Res<ExtractedSprites>,
+    surfaces_query: Query<&ExtractedSurface2d>,
+    mut surface_buffer: ResMut<Surface2dBuffer>,
+    resources: SurfaceResources,
+) {
+    let SurfaceResources {
+        images,
+        image_events,
+        pipeline,
+        mut texture_cache,
+        render_device,
+        render_queue,
+    } = resources;
+    let surface_buffer = &mut *surface_buffer;
+    surface_buffer.buffer.clear();
+
+    // Bind groups of modified or removed images refer to stale textures.
+    for event in &image_events.images {
+        if let AssetEvent::Modified { id } | AssetEvent::Removed { id } = event {
+            surface_buffer.texture_bind_groups.retain(
+                |(texture, normal_map, specular_map, emissive_map), _| {
+                    texture != id
+                        && *normal_map != Some(*id)
+                        && *specular_map != Some(*id)
+                        && *emissive_map != Some(*id)
+                },
+            );
+        }
+    }
+
+    let mut sprites = extracted_sprites.sprites.iter().collect::<Vec<_>>();
+    radsort::sort_by_key(&mut sprites, |(_, sprite)| sprite.transform.translation().z);
+
+    let mut drawn_textures = HashSet::new();
+
+    for (main_view_entity, main_view, visible_entities) in &main_views {
+        let visible_entities = visible_entities
+            .entities
+            .iter()
+            .copied()
+            .collect::<EntityHashSet>();
+        let mut draws = Vec::new();
+
+        // Sprites without surface draw the clear values of the buffers, so they are
+        // only needed to cover the surfaces below them.
+        let has_surfaces = sprites.iter().any(|(entity, sprite)| {
+            let entity = sprite.original_entity.unwrap_or(**entity);
+            visible_entities.contains(&entity) && surfaces_query.contains(entity)
+        });
+
+        for (entity, sprite) in &sprites {
+            if !has_surfaces
+                || !visible_entities.contains(&sprite.original_entity.unwrap_or(**entity))
+            {
+                continue;
+            }
+
+            let Some(sprite_image) = images.get(sprite.image_handle_id) else {
+                continue;
+            };
+            let surface = surfaces_query
+                .get(sprite.original_entity.unwrap_or(**entity))
+                .ok();
+            let get_map = |map: Option<AssetId<Image>>| {
+                map.and_then(|id| images.get(id).map(|image| (id, image)))
+            };
+            let normal_map = get_map(surface.and_then(|surface| surface.normal_map));
+            let specular_map = get_map(surface.and_then(|surface| surface.specular_map));
+            let emissive_map = get_map(surface.and_then(|surface| surface.emissive_map));
+
+            // Missing maps are replaced by the sprite texture and ignored with flags.
+            let textures = (
+                sprite.image_handle_id,
+                normal_map.map(|(id, _)| id),
+                specular_map.map(|(id, _)| id),
+                emissive_map.map(|(id, _)| id),
+            );
+            drawn_textures.insert(textures);
+            let texture_bind_group = surface_buffer
+                .texture_bind_groups
+                .entry(textures)
+                .or_insert_with(|| {
+                    render_device.create_bind_group(
+                        "surface_2d_texture_bind_group",
+                        &pipeline.texture_layout,
+                        &BindGroupEntries::sequential((
+                            &sprite_image.texture_view,
+                            &normal_map
+                                .map_or(sprite_image, |(_, image)| image)
+                                .texture_view,
+                            &specular_map
+                                .map_or(sprite_image, |(_, image)| image)
+                                .texture_view,
+                            &emissive_map
+                                .map_or(sprite_image, |(_, image)| image)
+                                .texture_view,
+                            &pipeline.sampler,
+                        )),
+                    )
+                })
+                .clone();
+
+            let (model, uv_rect, flip) = compute_sprite_quad(sprite, sprite_image.size);
+
+            let dynamic_offset = surface_buffer.buffer.push(&GpuSurface2d {
+                model,
+                uv_rect,
+                flip,
+                flags: normal_map.map_or(0, |_| SURFACE_FLAG_NORMAL_MAP)
+                    | specular_map.map_or(0, |_| SURFACE_FLAG_SPECULAR_MAP)
+                    | emissive_map.map_or(0, |_| SURFACE_FLAG_EMISSIVE_MAP),
+                emissive: surface.map_or(Vec4::ZERO, |surface| surface.emissive),
+                light_receiver: surface.map_or(1., |surface| surface.light_receiver),
+            });
+
+            draws.push(SurfaceDraw2d {
+                texture_bind_group,
+                dynamic_offset,
+            });
+        }
+
+        // A single pixel holding the clear values is enough for the main passes to sample.
+        let size = if has_surfaces {
+            main_view.viewport.zw()
+        } else {
+            UVec2::ONE
+        };
+        let mut get_texture = |label, format| {
+            texture_cache.get(
+                &render_device,
+                TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width: size.x,
+                        height: size.y,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+            )
+        };
+        let normal = get_texture("surface_2d_normal", SURFACE_NORMAL_FORMAT);
+        let material = get_texture("surface_2d_material", SURFACE_MATERIAL_FORMAT);
+        let emissive = get_texture("surface_2d_emissive", SURFACE_EMISSIVE_FORMAT);
+
+        commands.entity(main_view_entity).insert(ViewSurfaces2d {
+            // Flat and not normal mapped.
+            normal: ColorAttachment::new(normal, None, Some(Color::rgba_linear(0.5, 0.5, 0., 0.))),
+            material: ColorAttachment::new(
+                material,
+                None,
+                Some(Color::rgba_linear(0., 0., 1., 0.)),
+            ),
+            emissive: ColorAttachment::new(emissive, None, Some(Color::NONE)),
+            draws,
+        });
+    }
+
+    surface_buffer
+        .texture_bind_groups
+        .retain(|textures, _| drawn_textures.contains(textures));
+
+    surface_buffer
+        .buffer
+        .write_buffer(&render_device, &render_queue);
+    surface_buffer.bind_group = surface_buffer.buffer.binding().map(|binding| {
+        render_device.create_bind_group(
+            "surface_2d_bind_group",
+            &pipeline.surface_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+}
+
+pub struct Surface2dPassNode {
+    main_view_query: QueryState<(Read<ViewSurfaces2d>, Read<ViewUniformOffset>)>,
+}
+
+impl FromWorld for Surface2dPassNode {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            main_view_query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for Surface2dPassNode {
+    #[inline]
+    fn update(&mut self, world: &mut World) {
+        self.main_view_query.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Ok((surfaces, view_offset)) =
+            self.main_view_query.get_manual(world, graph.view_entity())
+        else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<Surface2dPipeline>();
+        let view_bind_group = world
+            .resource::<ViewUniforms>()
+            .uniforms
+            .binding()
+            .map(|binding| {
+                render_context.render_device().create_bind_group(
+                    "surface_2d_view_bind_group",
+                    &pipeline.view_layout,
+                    &BindGroupEntries::single(binding),
+                )
+            });
+
+        // Always begin the pass, so that the buffer gets cleared even if there's nothing to draw.
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("surface_2d_pass"),
+            color_attachments: &[
+                Some(surfaces.normal.get_attachment()),
+                Some(surfaces.material.get_attachment()),
+                Some(surfaces.emissive.get_attachment()),
+            ],
+            ..Default::default()
+        });
+
+        if surfaces.draws.is_empty() {
+            return Ok(());
+        }
+
+        let (Some(render_pipeline), Some(surface_bind_group), Some(view_bind_group)) = (
+            world
+                .resource::<PipelineCache>()
+                .get_render_pipeline(pipeline.cached_id),
+            world.resource::<Surface2dBuffer>().bind_group.as_ref(),
+            view_bind_group.as_ref(),
+        ) else {
+            return Ok(());
+        };
+
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, view_bind_group, &[view_offset.offset]);
+        for draw in &surfaces.draws {
+            render_pass.set_bind_group(1, &draw.texture_bind_group, &[]);
+            render_pass.set_bind_group(2, surface_bind_group, &[draw.dynamic_offset]);
+            render_pass.draw(0..6, 0..1);
+        }
+
+        Ok(())
+    }
+}
@@ -42,6 +42,25 @@ impl NumberBuffer {
         self.indices[number as usize]
     }
 
+    /// Makes sure numbers up to `count` (excluded) are in the buffer.
+    pub fn reserve(
+        &mut self,
+        count: u32,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+    ) {
+        if count as usize <= self.indices.len() {
+            return;
+        }
+
+        for i in self.indices.len() as u32..count {
+            let index = self.buffer.push(&i);
+            self.indices.push(index);
+        }
+
+        self.buffer.write_buffer(render_device, render_queue);
+    }
+
     #[inline]
     pub fn binding(&self) -> BindingResource {
         self.buffer.binding().unwrap()
@@ -8,20 +8,22 @@ use bevy::{
     transform::components::GlobalTransform,
 };
 
-use crate::ecs::{PointLight2d, SpotLight2d};
+use crate::ecs::{LineLight2d, PointLight2d, SpotLight2d};
 
 pub fn calc_light_bounds(
     commands: ParallelCommands,
     point_lights_query: Query<(Entity, &GlobalTransform, &PointLight2d)>,
     spot_lights_query: Query<(Entity, &GlobalTransform, &SpotLight2d)>,
+    line_lights_query: Query<(Entity, &LineLight2d)>,
 ) {
     point_lights_query
         .par_iter()
         .for_each(|(entity, transform, light)| {
+            let range = light.max_range();
             commands.command_scope(|mut c| {
                 c.entity(entity).insert(Aabb {
                     center: transform.translation().into(),
-                    half_extents: Vec3A::new(light.range, light.range, 1000.),
+                    half_extents: Vec3A::new(range, range, 1000.),
                 });
             });
         });
@@ -36,4 +38,14 @@ pub fn calc_light_bounds(
                 });
             });
         });
+
+    line_lights_query.par_iter().for_each(|(entity, light)| {
+        commands.command_scope(|mut c| {
+            // The segment follows the rotation of the entity, so the aabb is in local space.
+            c.entity(entity).insert(Aabb {
+                center: Vec3A::ZERO,
+                half_extents: Vec3A::new(light.length / 2. + light.range, light.range, 1000.),
+            });
+        });
+    });
 }
@@ -0,0 +1,192 @@
+use bevy::{
+    math::{Quat, UVec2, UVec4, Vec2, Vec3},
+    render::{
+        camera::{CameraProjection, OrthographicProjection, ScalingMode},
+        view::{ColorGrading, ExtractedView},
+    },
+    transform::components::{GlobalTransform, Transform},
+};
+use bevy_incandescent::render::{light::LightScreenSpace, ray_marching::get_sdf_size};
+
+const EPSILON: f32 = 1e-3;
+
+struct ViewCase {
+    scaling_mode: ScalingMode,
+    scale: f32,
+    viewport_origin: Vec2,
+    viewport_size: Vec2,
+    rotation: f32,
+}
+
+impl ViewCase {
+    fn extract(&self) -> ExtractedView {
+        let mut projection = OrthographicProjection {
+            scaling_mode: self.scaling_mode,
+            scale: self.scale,
+            viewport_origin: self.viewport_origin,
+            ..Default::default()
+        };
+        projection.update(self.viewport_size.x, self.viewport_size.y);
+
+        ExtractedView {
+            projection: projection.get_projection_matrix(),
+            transform: GlobalTransform::from(
+                Transform::from_xyz(120., -35., 0.)
+                    .with_rotation(Quat::from_rotation_z(self.rotation)),
+            ),
+            view_projection: None,
+            hdr: false,
+            viewport: UVec4::new(
+                0,
+                0,
+                self.viewport_size.x as u32,
+                self.viewport_size.y as u32,
+            ),
+            color_grading: ColorGrading::default(),
+        }
+    }
+}
+
+fn cases() -> Vec<ViewCase> {
+    let scaling_modes = [
+        ScalingMode::WindowSize(1.),
+        ScalingMode::Fixed {
+            width: 300.,
+            height: 300.,
+        },
+        ScalingMode::FixedHorizontal(500.),
+        ScalingMode::FixedVertical(500.),
+        ScalingMode::AutoMin {
+            min_width: 400.,
+            min_height: 200.,
+        },
+    ];
+    let viewport_sizes = [
+        Vec2::new(1280., 720.),
+        Vec2::new(720., 1280.),
+        Vec2::new(512., 512.),
+    ];
+
+    let mut cases = Vec::new();
+    for scaling_mode in scaling_modes {
+        for viewport_size in viewport_sizes {
+            for scale in [0.25, 1., 3.5] {
+                for rotation in [0., 0.7] {
+                    for viewport_origin in [Vec2::splat(0.5), Vec2::ZERO] {
+                        cases.push(ViewCase {
+                            scaling_mode,
+                            scale,
+                            viewport_origin,
+                            viewport_size,
+                            rotation,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    cases
+}
+
+/// The size of the view in world units, as computed by the shaders from `View::inverse_projection`.
+fn shader_screen_size(view: &ExtractedView) -> Vec2 {
+    let inverse_projection = view.projection.inverse();
+    2. * Vec2::new(inverse_projection.x_axis.x, inverse_projection.y_axis.y)
+}
+
+#[test]
+fn view_size_matches_shaders() {
+    for case in cases() {
+        let view = case.extract();
+        let screen_space = LightScreenSpace::new(&view);
+        assert!(
+            (screen_space.view_size() - shader_screen_size(&view)).length() < EPSILON,
+            "{:?} != {:?}",
+            screen_space.view_size(),
+            shader_screen_size(&view),
+        );
+    }
+}
+
+#[test]
+fn point_lights_stay_circular() {
+    let light_pos = Vec3::new(90., -10., 0.);
+    let range = 64.;
+
+    for case in cases() {
+        let view = case.extract();
+        let screen_space = LightScreenSpace::new(&view);
+        let screen_size = shader_screen_size(&view);
+
+        let light_px = screen_space.position(light_pos) * screen_size;
+        let range_px = screen_space.length(range) * screen_size.x;
+        assert!(
+            (range_px - range).abs() < EPSILON,
+            "range {} != {} with {:?}, scale {}, viewport {:?}",
+            range_px,
+            range,
+            case.scaling_mode,
+            case.scale,
+            case.viewport_size,
+        );
+
+        // Points at the edge of the light in world space are at its range in the shaders.
+        for dir in [
+            Vec2::X,
+            Vec2::NEG_X,
+            Vec2::Y,
+            Vec2::NEG_Y,
+            Vec2::ONE.normalize(),
+        ] {
+            let edge_px = screen_space.position(light_pos + (dir * range).extend(0.)) * screen_size;
+            assert!(
+                (edge_px.distance(light_px) - range_px).abs() < EPSILON,
+                "edge at {} != {} towards {:?} with {:?}, scale {}, viewport {:?}",
+                edge_px.distance(light_px),
+                range_px,
+                dir,
+                case.scaling_mode,
+                case.scale,
+                case.viewport_size,
+            );
+        }
+    }
+}
+
+#[test]
+fn directions_follow_camera_rotation() {
+    for case in cases() {
+        let view = case.extract();
+        let screen_space = LightScreenSpace::new(&view);
+        let screen_size = shader_screen_size(&view);
+
+        let origin = Vec3::new(10., 20., 0.);
+        let dir = Vec2::new(0.6, -0.8);
+        let expected = (screen_space.position(origin + dir.extend(0.) * 50.)
+            - screen_space.position(origin))
+            * screen_size;
+        let direction_ss = screen_space.direction(dir);
+        assert!(
+            (direction_ss * 50. - expected).length() < EPSILON,
+            "{:?} != {:?}",
+            direction_ss * 50.,
+            expected,
+        );
+    }
+}
+
+#[test]
+fn sdf_follows_viewport() {
+    for case in cases() {
+        let view = case.extract();
+        assert_eq!(
+            get_sdf_size(&view, 1.),
+            case.viewport_size.as_uvec2(),
+            "{:?}, scale {}",
+            case.scaling_mode,
+            case.scale,
+        );
+        assert_eq!(get_sdf_size(&view, 0.5), case.viewport_size.as_uvec2() / 2);
+        assert_eq!(get_sdf_size(&view, 0.), UVec2::ONE);
+    }
+}